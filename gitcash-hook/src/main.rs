@@ -0,0 +1,180 @@
+use std::{
+    collections::HashSet,
+    io::{self, BufRead},
+    process::ExitCode,
+};
+
+use anyhow::{anyhow, Context};
+use clap::Parser;
+use git2::{ErrorCode, Oid, Repository};
+use libgitcash::{compute_balances, find_violations, load_new_transactions, Error, RepoConfig};
+
+/// Server-side Git hook that validates pushed GitCash transactions.
+///
+/// Install it as `pre-receive` hook (updated refs are read from stdin) or as
+/// `update` hook (the updated ref is passed as arguments).
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Name of the updated ref (update hook only)
+    #[arg(requires_all = ["old", "new"])]
+    refname: Option<String>,
+    /// Old object name of the ref (update hook only)
+    old: Option<String>,
+    /// New object name of the ref (update hook only)
+    new: Option<String>,
+}
+
+/// A ref update received by the hook
+struct RefUpdate {
+    old: Oid,
+    new: Oid,
+    refname: String,
+}
+
+impl RefUpdate {
+    fn parse(refname: &str, old: &str, new: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            old: Oid::from_str(old).context(format!("Invalid old object name: {}", old))?,
+            new: Oid::from_str(new).context(format!("Invalid new object name: {}", new))?,
+            refname: refname.to_string(),
+        })
+    }
+}
+
+/// Read the ref updates, either from the arguments or from stdin
+fn read_updates(args: Args) -> anyhow::Result<Vec<RefUpdate>> {
+    if let (Some(refname), Some(old), Some(new)) = (&args.refname, &args.old, &args.new) {
+        return Ok(vec![RefUpdate::parse(refname, old, new)?]);
+    }
+    let mut updates = Vec::new();
+    for line in io::stdin().lock().lines() {
+        let line = line.context("Could not read hook input")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [old, new, refname] => updates.push(RefUpdate::parse(refname, old, new)?),
+            _ => return Err(anyhow!("Invalid hook input line: {}", line)),
+        }
+    }
+    Ok(updates)
+}
+
+/// Return the already accepted commits that a ref update builds on
+///
+/// For a new branch, these are the merge bases with all existing branches.
+fn known_commits(repository: &Repository, update: &RefUpdate) -> anyhow::Result<Vec<Oid>> {
+    if !update.old.is_zero() {
+        return Ok(vec![update.old]);
+    }
+    let mut known = Vec::new();
+    for reference in repository.references_glob("refs/heads/*")? {
+        if let Some(oid) = reference?.target() {
+            match repository.merge_base(update.new, oid) {
+                Ok(base) if !known.contains(&base) => known.push(base),
+                Ok(_) => {}
+                Err(e) if e.code() == ErrorCode::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+    Ok(known)
+}
+
+/// Return all commits that are introduced by a ref update
+fn new_commits(
+    repository: &Repository,
+    update: &RefUpdate,
+    known: &[Oid],
+) -> anyhow::Result<HashSet<Oid>> {
+    let mut revwalk = repository.revwalk()?;
+    revwalk.push(update.new)?;
+    for oid in known {
+        revwalk.hide(*oid)?;
+    }
+    Ok(revwalk.collect::<Result<_, _>>()?)
+}
+
+/// Validate a ref update, return a list of problems
+///
+/// Only branches are validated, deleting a ref is always allowed. Only the
+/// new commits must be valid, invalid commits that were accepted before are
+/// ignored.
+fn check_update(repository: &Repository, update: &RefUpdate) -> anyhow::Result<Vec<String>> {
+    if update.new.is_zero() || !update.refname.starts_with("refs/heads/") {
+        return Ok(vec![]);
+    }
+    let mut problems = Vec::new();
+
+    // The repo config must be valid
+    let tip = repository.find_commit(update.new)?;
+    let config = match RepoConfig::from_commit(repository, &tip) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            problems.push(e.to_string());
+            None
+        }
+    };
+
+    // All new transactions and reverts must be parseable
+    let known = known_commits(repository, update)?;
+    let transactions = match load_new_transactions(repository, update.new, &known) {
+        Ok(transactions) => transactions,
+        Err(e) => {
+            problems.push(e.to_string());
+            return Ok(problems);
+        }
+    };
+
+    // New transactions must follow the rules and may not overflow any
+    // balance (which requires a valid config)
+    if let Some(config) = config {
+        let new_commits = new_commits(repository, update, &known)?;
+        problems.extend(
+            find_violations(&transactions, &config)
+                .into_iter()
+                .filter(|violation| new_commits.contains(&violation.commit))
+                .map(|violation| violation.to_string()),
+        );
+        match compute_balances(&transactions, &config.currency.code) {
+            Err(Error::BalanceOverflowError(commit, account)) => {
+                if new_commits.contains(&commit) {
+                    problems.push(Error::BalanceOverflowError(commit, account).to_string());
+                }
+            }
+            Ok(_) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(problems)
+}
+
+fn run(args: Args) -> anyhow::Result<bool> {
+    let repository = Repository::open_from_env().context("Could not open repository")?;
+    let mut valid = true;
+    for update in read_updates(args)? {
+        let problems = check_update(&repository, &update)?;
+        if !problems.is_empty() {
+            valid = false;
+            eprintln!("❌ GitCash: Rejecting update of {}:", update.refname);
+            for problem in problems {
+                eprintln!("- {}", problem);
+            }
+        }
+    }
+    Ok(valid)
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("❌ GitCash: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use tempfile::TempDir;
+
+const CONFIG: &str = "name = \"Test\"\n\n[currency]\ncode = \"CHF\"\ndivisor = 100\n";
+
+/// A bare repository with the hook installed, and a clone of it
+struct Setup {
+    _dir: TempDir,
+    clone: PathBuf,
+    hook_path: PathBuf,
+}
+
+fn git(dir: &Path, args: &[&str]) -> Output {
+    Command::new("git")
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+impl Setup {
+    fn new(hook: &str) -> Self {
+        let dir = TempDir::new().unwrap();
+        let remote = dir.path().join("remote.git");
+        let clone = dir.path().join("clone");
+
+        // Create bare repository and install hook
+        assert!(git(dir.path(), &["init", "--bare", "remote.git"])
+            .status
+            .success());
+        let hook_path = remote.join("hooks").join(hook);
+        fs::write(
+            &hook_path,
+            format!(
+                "#!/bin/sh\nexec \"{}\" \"$@\"\n",
+                env!("CARGO_BIN_EXE_gitcash-hook")
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Clone it and push the config
+        let url = format!("file://{}", remote.display());
+        assert!(git(dir.path(), &["clone", &url, "clone"]).status.success());
+        fs::write(clone.join("gitcash.toml"), CONFIG).unwrap();
+        git(&clone, &["add", "gitcash.toml"]);
+        git(&clone, &["commit", "-m", "Initial commit"]);
+        let setup = Self {
+            _dir: dir,
+            clone,
+            hook_path,
+        };
+        assert!(setup.push().status.success());
+        setup
+    }
+
+    fn commit_transaction(&self, data: &str) {
+        let message = format!("Transaction: Test\n\n---\n{}\n---", data);
+        let output = git(&self.clone, &["commit", "--allow-empty", "-m", &message]);
+        assert!(output.status.success());
+    }
+
+    fn push(&self) -> Output {
+        git(&self.clone, &["push", "origin", "HEAD:refs/heads/main"])
+    }
+
+    /// Push with the hook temporarily disabled, e.g. to simulate commits
+    /// from before the hook was installed
+    fn push_without_hook(&self) -> Output {
+        let disabled = self.hook_path.with_extension("disabled");
+        fs::rename(&self.hook_path, &disabled).unwrap();
+        let output = self.push();
+        fs::rename(&disabled, &self.hook_path).unwrap();
+        output
+    }
+}
+
+#[test]
+fn test_valid_push() {
+    for hook in ["pre-receive", "update"] {
+        let setup = Setup::new(hook);
+        setup.commit_transaction("from = \"source:cash\"\nto = \"user:a\"\namount = 2000");
+        setup.commit_transaction("from = \"user:a\"\nto = \"pos:fridge\"\namount = 250");
+        let output = setup.push();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+#[test]
+fn test_rejected_push() {
+    for (data, expected) in [
+        (
+            "from = \"user:a\"\nto = \"pos:fridge\"",
+            "missing field `amount`",
+        ),
+        (
+            "from = \"pos:fridge\"\nto = \"user:a\"\namount = 250",
+            "Point of sale accounts cannot send money",
+        ),
+    ] {
+        let setup = Setup::new("pre-receive");
+        setup.commit_transaction(data);
+        let output = setup.push();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success());
+        assert!(stderr.contains(expected), "{}", stderr);
+    }
+}
+
+#[test]
+fn test_invalid_legacy_commit() {
+    let setup = Setup::new("pre-receive");
+    setup.commit_transaction("from = \"user:a\"\nto = \"pos:fridge\"");
+    assert!(setup.push_without_hook().status.success());
+
+    // Later pushes are only validated against the new commits
+    setup.commit_transaction("from = \"user:a\"\nto = \"pos:fridge\"\namount = 250");
+    let output = setup.push();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    setup.commit_transaction("from = \"pos:fridge\"\nto = \"user:a\"\namount = 250");
+    assert!(!setup.push().status.success());
+}
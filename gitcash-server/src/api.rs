@@ -0,0 +1,214 @@
+use std::{io::Read, sync::Mutex};
+
+use libgitcash::{Account, Error, Repo, Transaction, TransactionOptions};
+use serde::Serialize;
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response};
+use tracing::{debug, warn};
+
+/// Maximum size of a request body in bytes
+const MAX_BODY_SIZE: u64 = 64 * 1024;
+
+/// An error response
+#[derive(Debug)]
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        let status = match &error {
+            Error::TransactionParseError(_) | Error::AmountParseError(_) => 400,
+            Error::ValidationError(_) | Error::RevertError(_) | Error::CreditLimitError { .. } => {
+                422
+            }
+            _ => 500,
+        };
+        Self::new(status, error.to_string())
+    }
+}
+
+/// A successful response
+struct ApiResponse {
+    status: u16,
+    body: serde_json::Value,
+}
+
+impl ApiResponse {
+    fn ok(body: impl Serialize) -> Result<Self, ApiError> {
+        Self::with_status(200, body)
+    }
+
+    fn with_status(status: u16, body: impl Serialize) -> Result<Self, ApiError> {
+        let body = serde_json::to_value(body)
+            .map_err(|e| ApiError::new(500, format!("Could not serialize response: {}", e)))?;
+        Ok(Self { status, body })
+    }
+}
+
+#[derive(Serialize)]
+struct AccountBalance {
+    account: Account,
+    currency: String,
+    balance: i64,
+}
+
+/// Handle a request and send the JSON response
+///
+/// The request body is read first, then the repository is locked for the
+/// rest of the request, and reloaded if its HEAD was moved, so that changes
+/// made by other processes (e.g. a sync) are picked up.
+pub fn handle(mut request: Request, repo: &Mutex<Repo>) {
+    debug!("{} {}", request.method(), request.url());
+    let result = read_body(&mut request).and_then(|body| {
+        let mut repo = repo.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        repo.reload_if_changed()
+            .map_err(ApiError::from)
+            .and_then(|_| route(&request, &body, &mut repo))
+    });
+    let (status, body) = match result {
+        Ok(response) => (response.status, response.body),
+        Err(e) => {
+            if e.status >= 500 {
+                warn!("{} {}: {}", request.method(), request.url(), e.message);
+            }
+            (e.status, json!({ "error": e.message }))
+        }
+    };
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                .expect("Invalid header"),
+        );
+    if let Err(e) = request.respond(response) {
+        warn!("Could not send response: {}", e);
+    }
+}
+
+/// Read the request body, which may not exceed [`MAX_BODY_SIZE`]
+fn read_body(request: &mut Request) -> Result<String, ApiError> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_SIZE + 1)
+        .read_to_string(&mut body)
+        .map_err(|e| ApiError::new(400, format!("Could not read request body: {}", e)))?;
+    if body.len() as u64 > MAX_BODY_SIZE {
+        return Err(ApiError::new(
+            413,
+            format!("Request body exceeds {} bytes", MAX_BODY_SIZE),
+        ));
+    }
+    Ok(body)
+}
+
+fn route(request: &Request, body: &str, repo: &mut Repo) -> Result<ApiResponse, ApiError> {
+    let url = request.url().to_string();
+    let path = url.split('?').next().unwrap_or_default();
+    let segments = path
+        .trim_matches('/')
+        .split('/')
+        .map(percent_decode)
+        .collect::<Result<Vec<_>, _>>()?;
+    let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+    match (request.method(), &segments[..]) {
+        (Method::Get, ["accounts"]) => list_accounts(repo),
+        (Method::Get, ["accounts", account]) => get_account(repo, account),
+        (Method::Get, ["balances"]) => list_balances(repo),
+        (Method::Post, ["transactions"]) => create_transaction(body, repo),
+        (_, ["accounts"] | ["accounts", _] | ["balances"] | ["transactions"]) => {
+            Err(ApiError::new(405, "Method not allowed"))
+        }
+        _ => Err(ApiError::new(404, "Not found")),
+    }
+}
+
+/// GET /accounts: List all accounts
+fn list_accounts(repo: &Repo) -> Result<ApiResponse, ApiError> {
+    let mut accounts = repo.accounts().into_iter().collect::<Vec<_>>();
+    accounts.sort_by_key(|account| account.to_string());
+    ApiResponse::ok(accounts)
+}
+
+/// GET /accounts/{account}: Return the balance of an account in the primary
+/// currency
+fn get_account(repo: &Repo, account: &str) -> Result<ApiResponse, ApiError> {
+    let account = Account::try_from(account.to_string())?;
+    let currency = repo.config().currency.code.clone();
+    let balance = repo
+        .balances_in(&currency)?
+        .get(&account)
+        .copied()
+        .ok_or_else(|| ApiError::new(404, format!("Unknown account: {}", account)))?;
+    ApiResponse::ok(AccountBalance {
+        account,
+        currency,
+        balance,
+    })
+}
+
+/// GET /balances: List all accounts and their balances in all currencies
+fn list_balances(repo: &Repo) -> Result<ApiResponse, ApiError> {
+    let mut balances = repo.balances()?;
+    let mut entries = Vec::new();
+    for currency in repo.config().currencies() {
+        let mut accounts = balances
+            .remove(&currency.code)
+            .unwrap_or_default()
+            .into_iter()
+            .collect::<Vec<_>>();
+        accounts.sort_by_key(|(account, _)| account.to_string());
+        entries.extend(
+            accounts
+                .into_iter()
+                .map(|(account, balance)| AccountBalance {
+                    account,
+                    currency: currency.code.clone(),
+                    balance,
+                }),
+        );
+    }
+    ApiResponse::ok(entries)
+}
+
+/// POST /transactions: Create a transaction, return the created record
+///
+/// The transaction is checked the same way as by the CLI.
+fn create_transaction(body: &str, repo: &mut Repo) -> Result<ApiResponse, ApiError> {
+    let transaction: Transaction = serde_json::from_str(body)
+        .map_err(|e| ApiError::new(400, format!("Invalid transaction: {}", e)))?;
+    repo.check_transaction(&transaction, &TransactionOptions::default())?;
+    repo.create_transaction(transaction)?;
+    ApiResponse::with_status(201, repo.transactions().last())
+}
+
+/// Decode a percent-encoded URL path segment
+fn percent_decode(segment: &str) -> Result<String, ApiError> {
+    let invalid = || ApiError::new(400, format!("Invalid URL path segment: {}", segment));
+    let mut bytes = Vec::with_capacity(segment.len());
+    let mut input = segment.bytes();
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let hex = [
+                input.next().ok_or_else(invalid)?,
+                input.next().ok_or_else(invalid)?,
+            ];
+            let hex = std::str::from_utf8(&hex).map_err(|_| invalid())?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
@@ -0,0 +1,91 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use anyhow::anyhow;
+use clap::Parser;
+use libgitcash::{LoadMode, OpenOptions, Repo};
+use tiny_http::Server;
+use tracing::{info, metadata::LevelFilter, warn};
+
+mod api;
+
+/// HTTP API for nodes that cannot access the GitCash repository themselves.
+///
+/// All requests are served from a single repository, access to it is
+/// serialized.
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Path to the GitCash repository
+    #[arg(short, long)]
+    repo: PathBuf,
+
+    /// Address to listen on
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// Number of worker threads
+    #[arg(long, default_value_t = 4)]
+    threads: usize,
+
+    /// Cache parsed transactions in the `.git` directory
+    #[arg(long)]
+    cache: bool,
+
+    /// Skip invalid commits instead of refusing to start
+    #[arg(long)]
+    lenient: bool,
+}
+
+pub fn main() -> anyhow::Result<()> {
+    // Initialize logging subscriber
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(LevelFilter::INFO)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber).expect("Could not set tracing subscriber");
+
+    let args = Args::parse();
+
+    // Open repo
+    let options = OpenOptions {
+        cache: args.cache,
+        mode: if args.lenient {
+            LoadMode::Lenient
+        } else {
+            LoadMode::Strict
+        },
+    };
+    let repo = Repo::open_with_options(&args.repo, options)?;
+    for diagnostic in repo.diagnostics() {
+        warn!("{}", diagnostic);
+    }
+    let repo = Arc::new(Mutex::new(repo));
+
+    // Serve requests
+    let server = Arc::new(
+        Server::http(&args.listen)
+            .map_err(|e| anyhow!("Could not listen on {}: {}", args.listen, e))?,
+    );
+    info!("Listening on {}", args.listen);
+    let workers = (0..args.threads.max(1))
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let repo = Arc::clone(&repo);
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    api::handle(request, &repo);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        worker
+            .join()
+            .map_err(|_| anyhow!("Worker thread panicked"))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,213 @@
+use std::{
+    fs,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    process::{Child, Command},
+    thread,
+    time::Duration,
+};
+
+use tempfile::TempDir;
+
+const CONFIG: &str = "name = \"Test\"\n\n[currency]\ncode = \"CHF\"\ndivisor = 100\n\n\
+                      [limits]\nuser = -500\n";
+
+/// A repository with a running server
+struct Setup {
+    dir: TempDir,
+    server: Child,
+    port: u16,
+}
+
+const DEPOSIT: &str =
+    "Transaction: Deposit\n\n---\nfrom = \"source:cash\"\nto = \"user:a\"\namount = 2000\n---";
+
+fn git(dir: &Path, args: &[&str]) {
+    let output = Command::new("git")
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+impl Setup {
+    fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        git(dir.path(), &["init"]);
+        fs::write(dir.path().join("gitcash.toml"), CONFIG).unwrap();
+        git(dir.path(), &["add", "gitcash.toml"]);
+        git(dir.path(), &["commit", "-m", "Initial commit"]);
+        git(dir.path(), &["commit", "--allow-empty", "-m", DEPOSIT]);
+
+        // Find a free port and start the server
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let server = Command::new(env!("CARGO_BIN_EXE_gitcash-server"))
+            .arg("--repo")
+            .arg(dir.path())
+            .arg("--listen")
+            .arg(format!("127.0.0.1:{}", port))
+            .spawn()
+            .unwrap();
+        let setup = Self { dir, server, port };
+        for _ in 0..100 {
+            if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                return setup;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        panic!("Server did not start");
+    }
+
+    /// Send a request, return status code and body
+    fn request(&self, method: &str, path: &str, body: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.port)).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split("\r\n\r\n").nth(1).unwrap().to_string();
+        (status, body)
+    }
+}
+
+impl Drop for Setup {
+    fn drop(&mut self) {
+        let _ = self.server.kill();
+        let _ = self.server.wait();
+    }
+}
+
+#[test]
+fn test_api() {
+    let setup = Setup::new();
+
+    let (status, body) = setup.request("GET", "/accounts", "");
+    assert_eq!(status, 200);
+    assert_eq!(body, r#"["source:cash","user:a"]"#);
+
+    let (status, body) = setup.request("GET", "/accounts/user%3Aa", "");
+    assert_eq!(status, 200);
+    assert_eq!(
+        body,
+        r#"{"account":"user:a","balance":2000,"currency":"CHF"}"#
+    );
+    assert_eq!(setup.request("GET", "/accounts/user:b", "").0, 404);
+    assert_eq!(setup.request("GET", "/unknown", "").0, 404);
+
+    // Create transaction
+    let purchase = r#"{"from": "user:a", "to": "pos:fridge", "amount": 250}"#;
+    let (status, body) = setup.request("POST", "/transactions", purchase);
+    assert_eq!(status, 201, "{}", body);
+    assert!(body.contains(r#""title":"User a pays 2.50 CHF to PointOfSale fridge""#));
+    let (_, body) = setup.request("GET", "/balances", "");
+    assert_eq!(
+        body,
+        r#"[{"account":"pos:fridge","balance":250,"currency":"CHF"},{"account":"source:cash","balance":-2000,"currency":"CHF"},{"account":"user:a","balance":1750,"currency":"CHF"}]"#
+    );
+
+    // Invalid transactions
+    let (status, body) = setup.request("POST", "/transactions", "{}");
+    assert_eq!(status, 400);
+    assert!(body.starts_with(r#"{"error":"Invalid transaction"#));
+    let refund = r#"{"from": "pos:fridge", "to": "user:a", "amount": 250}"#;
+    assert_eq!(setup.request("POST", "/transactions", refund).0, 422);
+    assert_eq!(setup.request("DELETE", "/transactions", "").0, 405);
+
+    // The transaction was committed to the repository
+    let log = Command::new("git")
+        .args(["log", "-1", "--format=%s"])
+        .current_dir(setup.dir.path())
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&log.stdout).trim(),
+        "Transaction: User a pays 2.50 CHF to PointOfSale fridge"
+    );
+}
+
+#[test]
+fn test_rejected_transactions() {
+    let setup = Setup::new();
+    for (body, status) in [
+        (
+            r#"{"from": "user:a", "to": "pos:fridge", "amount": 0}"#,
+            422,
+        ),
+        (
+            r#"{"from": "user:a", "to": "pos:fridge", "amount": -250}"#,
+            422,
+        ),
+        (
+            r#"{"from": "user:b", "to": "pos:fridge", "amount": 250}"#,
+            422,
+        ),
+        (
+            r#"{"from": "user:a", "to": "pos:fridge", "amount": 2600}"#,
+            422,
+        ),
+    ] {
+        let (actual, response) = setup.request("POST", "/transactions", body);
+        assert_eq!(actual, status, "{}: {}", body, response);
+    }
+    let too_large = format!("{{\"description\": \"{}\"}}", "x".repeat(100_000));
+    assert_eq!(setup.request("POST", "/transactions", &too_large).0, 413);
+
+    // Nothing was committed
+    let (_, body) = setup.request("GET", "/accounts/user:a", "");
+    assert_eq!(
+        body,
+        r#"{"account":"user:a","balance":2000,"currency":"CHF"}"#
+    );
+}
+
+#[test]
+fn test_external_commits() {
+    let setup = Setup::new();
+    assert_eq!(setup.request("GET", "/accounts", "").0, 200);
+
+    // Commits by other processes are picked up
+    git(
+        setup.dir.path(),
+        &["commit", "--allow-empty", "-m", DEPOSIT],
+    );
+    let (_, body) = setup.request("GET", "/accounts/user:a", "");
+    assert_eq!(
+        body,
+        r#"{"account":"user:a","balance":4000,"currency":"CHF"}"#
+    );
+}
+
+#[test]
+fn test_stalled_client() {
+    let setup = Setup::new();
+
+    // A client that never sends the announced body doesn't block others (the
+    // body is larger than what tiny_http reads before dispatching a request)
+    let mut stalled = TcpStream::connect(("127.0.0.1", setup.port)).unwrap();
+    write!(
+        stalled,
+        "POST /transactions HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10000\r\n\r\n{{"
+    )
+    .unwrap();
+    thread::sleep(Duration::from_millis(200));
+    let (status, _) = setup.request("GET", "/balances", "");
+    assert_eq!(status, 200);
+}
@@ -0,0 +1,127 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, NaiveDate};
+use libgitcash::{Account, AccountInfo, Error, Repo};
+
+/// Return the current date (UTC), used as creation date of new accounts
+pub fn today() -> Option<NaiveDate> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|duration| DateTime::from_timestamp(duration.as_secs() as i64, 0))
+        .map(|time| time.date_naive())
+}
+
+/// Return the registry entry of an existing account, or a new one if it is
+/// not registered yet
+fn account_info(repo: &Repo, account: &Account) -> Result<AccountInfo, Error> {
+    if !repo.accounts().contains(account) {
+        return Err(Error::ValidationError(format!(
+            "Unknown account: {}",
+            account
+        )));
+    }
+    Ok(repo
+        .account_info(account)
+        .cloned()
+        .unwrap_or_else(|| AccountInfo::new(account.clone())))
+}
+
+/// Change the display name and/or contact of an account, an empty value
+/// removes it
+pub fn edit(
+    repo: &mut Repo,
+    account: &Account,
+    display_name: Option<String>,
+    contact: Option<String>,
+) -> Result<(), Error> {
+    if display_name.is_none() && contact.is_none() {
+        return Err(Error::ValidationError(
+            "Nothing to change, pass --display-name and/or --contact".into(),
+        ));
+    }
+    let mut info = account_info(repo, account)?;
+    if let Some(display_name) = display_name {
+        info.display_name = Some(display_name).filter(|value| !value.is_empty());
+    }
+    if let Some(contact) = contact {
+        info.contact = Some(contact).filter(|value| !value.is_empty());
+    }
+    repo.set_account_info(info)?;
+    println!("✅ Updated account {}", account);
+    Ok(())
+}
+
+/// Archive an account (e.g. of a member who left), or restore it
+pub fn archive(repo: &mut Repo, account: &Account, archived: bool) -> Result<(), Error> {
+    let mut info = account_info(repo, account)?;
+    if info.archived == archived {
+        return Err(Error::ValidationError(format!(
+            "Account {} is {}archived",
+            account,
+            if archived { "already " } else { "not " }
+        )));
+    }
+    info.archived = archived;
+    repo.set_account_info(info)?;
+    match archived {
+        true => println!("✅ Archived account {}", account),
+        false => println!("✅ Restored account {}", account),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::{TestRepo, DEPOSIT},
+        transactions::add_user,
+    };
+
+    #[test]
+    fn test_add_user() {
+        let test_repo = TestRepo::new();
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        add_user(&mut repo, "danilo", None, None, false).unwrap();
+
+        let info = repo
+            .account_info(&Account::user("danilo").unwrap())
+            .unwrap();
+        assert_eq!(info.created, today());
+        assert_eq!(info.display_name, None);
+    }
+
+    #[test]
+    fn test_edit_and_archive() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        let danilo = Account::user("danilo").unwrap();
+
+        // Accounts that were never registered can be edited as well
+        edit(&mut repo, &danilo, Some("Danilo".into()), None).unwrap();
+        edit(&mut repo, &danilo, None, Some("mail@example.com".into())).unwrap();
+        let info = repo.account_info(&danilo).unwrap();
+        assert_eq!(info.display_name.as_deref(), Some("Danilo"));
+        assert_eq!(info.contact.as_deref(), Some("mail@example.com"));
+        assert_eq!(info.created, None);
+        edit(&mut repo, &danilo, Some(String::new()), None).unwrap();
+        assert_eq!(repo.account_info(&danilo).unwrap().display_name, None);
+        assert!(edit(&mut repo, &danilo, None, None).is_err());
+
+        archive(&mut repo, &danilo, true).unwrap();
+        assert!(repo.account_info(&danilo).unwrap().archived);
+        assert!(archive(&mut repo, &danilo, true).is_err());
+        archive(&mut repo, &danilo, false).unwrap();
+        assert!(!repo.account_info(&danilo).unwrap().archived);
+
+        // The changes are committed
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        let info = repo.account_info(&danilo).unwrap();
+        assert_eq!(info.contact.as_deref(), Some("mail@example.com"));
+
+        let unknown = Account::user("nobody").unwrap();
+        assert!(archive(&mut repo, &unknown, true).is_err());
+    }
+}
@@ -1,7 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context};
-use libgitcash::{Account, AccountType};
+use libgitcash::{Account, AccountType, LoadMode};
 use serde::{Deserialize, Serialize};
 
 /// GitCash configuration
@@ -17,6 +17,112 @@ pub struct Config {
     pub git_name: String,
     /// E-mail to use for git commits
     pub git_email: String,
+
+    /// Name of the Git remote to sync with
+    #[serde(default = "default_remote")]
+    pub remote: String,
+
+    /// Cache parsed transactions to speed up loading the repository
+    #[serde(default)]
+    pub cache: bool,
+
+    /// Whether invalid commits abort loading ("strict") or are skipped with a
+    /// warning ("lenient")
+    #[serde(default)]
+    pub load_mode: LoadMode,
+
+    /// Minutes during which purchases can be undone in the interactive CLI
+    #[serde(default = "default_undo_window")]
+    pub undo_window: u64,
+
+    /// Configuration of the `watch` command
+    #[serde(default)]
+    pub watch: WatchConfig,
+
+    /// Configuration of the `export` command
+    #[serde(default)]
+    pub export: ExportConfig,
+}
+
+fn default_remote() -> String {
+    "origin".into()
+}
+
+fn default_undo_window() -> u64 {
+    10
+}
+
+/// Configuration of the `watch` command
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WatchConfig {
+    /// File storing the ID of the last processed commit
+    #[serde(default = "default_state_file")]
+    pub state_file: PathBuf,
+
+    /// Seconds to wait between checks for new transactions
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+
+    /// Sync with the remote before every check
+    #[serde(default)]
+    pub sync: bool,
+
+    /// Where to send events for new transactions
+    #[serde(default, rename = "sink")]
+    pub sinks: Vec<Sink>,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            state_file: default_state_file(),
+            interval: default_interval(),
+            sync: false,
+            sinks: Vec::new(),
+        }
+    }
+}
+
+fn default_state_file() -> PathBuf {
+    "watch-state".into()
+}
+
+fn default_interval() -> u64 {
+    60
+}
+
+/// Configuration of the `export` command
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// Parent account of user accounts in the journal
+    pub user: String,
+    /// Parent account of point of sale accounts in the journal
+    pub pos: String,
+    /// Parent account of source accounts in the journal
+    pub source: String,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            user: "Liabilities:Members".into(),
+            pos: "Income:Sales".into(),
+            source: "Assets".into(),
+        }
+    }
+}
+
+/// Destination for events, each event is serialized as JSON
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Sink {
+    /// Run a program with the event on stdin
+    Script { command: PathBuf },
+    /// Append the event as a line to a file
+    File { path: PathBuf },
+    /// POST the event to a URL
+    Webhook { url: String },
 }
 
 impl Config {
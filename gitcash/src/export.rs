@@ -0,0 +1,364 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use anyhow::anyhow;
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use libgitcash::{Account, AccountType, Currency, Repo, TransactionRecord};
+
+use crate::config::ExportConfig;
+
+/// Output format of exports
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Ledger / hledger journal
+    #[default]
+    Ledger,
+    /// Beancount journal
+    Beancount,
+    /// CSV with one row per posting (amounts as decimal numbers)
+    Csv,
+}
+
+/// A transaction as journal entry
+struct Entry<'a> {
+    record: &'a TransactionRecord,
+    date: NaiveDate,
+    lines: Vec<Line<'a>>,
+}
+
+/// A single line (posting) of a journal entry
+struct Line<'a> {
+    account: &'a Account,
+    currency: &'a Currency,
+    /// The amount in accounting convention (negative if the gitcash balance
+    /// of the account increases, e.g. for income or money owed to a member)
+    amount: i64,
+}
+
+impl Entry<'_> {
+    /// Description of the entry, falls back to the commit title
+    ///
+    /// Line breaks are replaced by spaces, as journal entries start with a
+    /// single header line.
+    fn description(&self) -> String {
+        let description = self
+            .record
+            .transaction
+            .description
+            .as_deref()
+            .unwrap_or(&self.record.title);
+        description.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Metadata of the entry as key/value pairs
+    fn metadata(&self) -> Vec<(&'static str, String)> {
+        let mut metadata = vec![("commit", self.record.id.to_string())];
+        if let Some(meta) = &self.record.transaction.meta {
+            if let Some(class) = &meta.class {
+                metadata.push(("class", class.clone()));
+            }
+            if let Some(ean) = meta.ean {
+                metadata.push(("ean", ean.to_string()));
+            }
+        }
+        metadata
+    }
+}
+
+/// Build the journal entries of all non-reverted transactions
+fn entries(repo: &Repo) -> anyhow::Result<Vec<Entry<'_>>> {
+    let config = repo.config();
+    let mut entries = Vec::new();
+    for record in repo.transactions().iter().filter(|r| !r.is_reverted()) {
+        let mut lines = Vec::new();
+        for posting in record.transaction.postings() {
+            let currency = config
+                .find_currency(posting.currency)
+                .ok_or_else(|| anyhow!("Unknown currency in transaction {}", record.id))?;
+            lines.push(Line {
+                account: posting.to,
+                currency,
+                amount: -posting.amount,
+            });
+            lines.push(Line {
+                account: posting.from,
+                currency,
+                amount: posting.amount,
+            });
+        }
+        entries.push(Entry {
+            record,
+            date: record.time.date_naive(),
+            lines,
+        });
+    }
+    Ok(entries)
+}
+
+/// Return the journal account name of an account, e.g. "Income:Sales:fridge"
+///
+/// Beancount requires every component to start with a capital letter.
+fn account_name(config: &ExportConfig, account: &Account, capitalize: bool) -> String {
+    let parent = match account.account_type {
+        AccountType::User => &config.user,
+        AccountType::PointOfSale => &config.pos,
+        AccountType::Source => &config.source,
+    };
+    let mut chars = account.name.chars();
+    let name = match (capitalize, chars.next()) {
+        (true, Some(first)) => first.to_uppercase().chain(chars).collect(),
+        _ => account.name.clone(),
+    };
+    format!("{}:{}", parent, name)
+}
+
+/// Quote a string for beancount
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn write_ledger(out: &mut impl Write, entries: &[Entry], config: &ExportConfig) -> io::Result<()> {
+    for entry in entries {
+        writeln!(out, "{} * {}", entry.date, entry.description())?;
+        for (key, value) in entry.metadata() {
+            writeln!(out, "    ; {}: {}", key, value)?;
+        }
+        for line in &entry.lines {
+            writeln!(
+                out,
+                "    {}  {} {}",
+                account_name(config, line.account, false),
+                line.currency.format_decimal(line.amount),
+                line.currency.code
+            )?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_beancount(
+    out: &mut impl Write,
+    entries: &[Entry],
+    config: &ExportConfig,
+) -> io::Result<()> {
+    // Beancount requires accounts to be opened before they are used
+    let mut opened = BTreeMap::new();
+    for entry in entries {
+        for line in &entry.lines {
+            opened
+                .entry(account_name(config, line.account, true))
+                .or_insert(entry.date);
+        }
+    }
+    for (account, date) in &opened {
+        writeln!(out, "{} open {}", date, account)?;
+    }
+    writeln!(out)?;
+
+    for entry in entries {
+        writeln!(out, "{} * {}", entry.date, quote(&entry.description()))?;
+        for (key, value) in entry.metadata() {
+            writeln!(out, "  {}: {}", key, quote(&value))?;
+        }
+        for line in &entry.lines {
+            writeln!(
+                out,
+                "  {}  {} {}",
+                account_name(config, line.account, true),
+                line.currency.format_decimal(line.amount),
+                line.currency.code
+            )?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_csv(out: impl Write, entries: &[Entry], config: &ExportConfig) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record([
+        "date",
+        "commit",
+        "description",
+        "account",
+        "amount",
+        "currency",
+        "class",
+        "ean",
+    ])?;
+    for entry in entries {
+        let meta = entry.record.transaction.meta.as_ref();
+        let class = meta.and_then(|meta| meta.class.clone()).unwrap_or_default();
+        let ean = meta
+            .and_then(|meta| meta.ean)
+            .map(|ean| ean.to_string())
+            .unwrap_or_default();
+        for line in &entry.lines {
+            writer.write_record([
+                &entry.date.to_string(),
+                &entry.record.id.to_string(),
+                &entry.description(),
+                &account_name(config, line.account, false),
+                &line.currency.format_decimal(line.amount),
+                &line.currency.code,
+                &class,
+                &ean,
+            ])?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write all non-reverted transactions as journal to stdout
+///
+/// Every transaction becomes one entry, dated with the commit date. Amounts
+/// follow accounting conventions, i.e. money that members deposited shows
+/// up as negative (credit) balance of their liability account.
+pub fn export(repo: &Repo, config: &ExportConfig, format: ExportFormat) -> anyhow::Result<()> {
+    let entries = entries(repo)?;
+    let mut out = io::stdout().lock();
+    match format {
+        ExportFormat::Ledger => write_ledger(&mut out, &entries, config)?,
+        ExportFormat::Beancount => write_beancount(&mut out, &entries, config)?,
+        ExportFormat::Csv => write_csv(out, &entries, config)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Oid;
+    use libgitcash::Repo;
+
+    use super::*;
+    use crate::test_utils::{TestRepo, DEPOSIT};
+
+    /// 2023-08-15 12:00:00 UTC
+    const TIME: i64 = 1_692_100_800;
+
+    const PURCHASE: &str = "from = \"user:danilo\"\nto = \"pos:fridge\"\namount = 305\n\n\
+                            [meta]\nclass = \"softdrink\"\nean = 7610867035003";
+    const EXCHANGE: &str = "from = \"user:danilo\"\nto = \"user:bank\"\namount = 1000\n\n\
+                            [exchange]\ncurrency = \"EUR\"\namount = 950\nrate = \"0.95\"";
+
+    /// Export a deposit, a purchase with decimal places and an exchange
+    fn export(format: ExportFormat) -> (String, [Oid; 3]) {
+        let test_repo = TestRepo::new();
+        let ids = [
+            test_repo.commit_transaction_at("Deposit", DEPOSIT, TIME),
+            test_repo.commit_transaction_at("User danilo bought Vivi Kola", PURCHASE, TIME),
+            test_repo.commit_transaction_at("Exchange", EXCHANGE, TIME + 86_400),
+        ];
+        let repo = Repo::open(test_repo.path()).unwrap();
+        let entries = entries(&repo).unwrap();
+        let config = ExportConfig::default();
+        let mut out = Vec::new();
+        match format {
+            ExportFormat::Ledger => write_ledger(&mut out, &entries, &config).unwrap(),
+            ExportFormat::Beancount => write_beancount(&mut out, &entries, &config).unwrap(),
+            ExportFormat::Csv => write_csv(&mut out, &entries, &config).unwrap(),
+        }
+        (String::from_utf8(out).unwrap(), ids)
+    }
+
+    #[test]
+    fn test_ledger() {
+        let (output, [deposit, purchase, exchange]) = export(ExportFormat::Ledger);
+        let expected = format!(
+            "2023-08-15 * Deposit\n\
+             \x20   ; commit: {deposit}\n\
+             \x20   Liabilities:Members:danilo  -20.00 CHF\n\
+             \x20   Assets:cash  20.00 CHF\n\
+             \n\
+             2023-08-15 * User danilo bought Vivi Kola\n\
+             \x20   ; commit: {purchase}\n\
+             \x20   ; class: softdrink\n\
+             \x20   ; ean: 7610867035003\n\
+             \x20   Income:Sales:fridge  -3.05 CHF\n\
+             \x20   Liabilities:Members:danilo  3.05 CHF\n\
+             \n\
+             2023-08-16 * Exchange\n\
+             \x20   ; commit: {exchange}\n\
+             \x20   Liabilities:Members:bank  -10.00 CHF\n\
+             \x20   Liabilities:Members:danilo  10.00 CHF\n\
+             \x20   Liabilities:Members:danilo  -9.50 EUR\n\
+             \x20   Liabilities:Members:bank  9.50 EUR\n\
+             \n"
+        );
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_beancount() {
+        let (output, [deposit, purchase, exchange]) = export(ExportFormat::Beancount);
+        let expected = format!(
+            "2023-08-15 open Assets:Cash\n\
+             2023-08-15 open Income:Sales:Fridge\n\
+             2023-08-16 open Liabilities:Members:Bank\n\
+             2023-08-15 open Liabilities:Members:Danilo\n\
+             \n\
+             2023-08-15 * \"Deposit\"\n\
+             \x20 commit: \"{deposit}\"\n\
+             \x20 Liabilities:Members:Danilo  -20.00 CHF\n\
+             \x20 Assets:Cash  20.00 CHF\n\
+             \n\
+             2023-08-15 * \"User danilo bought Vivi Kola\"\n\
+             \x20 commit: \"{purchase}\"\n\
+             \x20 class: \"softdrink\"\n\
+             \x20 ean: \"7610867035003\"\n\
+             \x20 Income:Sales:Fridge  -3.05 CHF\n\
+             \x20 Liabilities:Members:Danilo  3.05 CHF\n\
+             \n\
+             2023-08-16 * \"Exchange\"\n\
+             \x20 commit: \"{exchange}\"\n\
+             \x20 Liabilities:Members:Bank  -10.00 CHF\n\
+             \x20 Liabilities:Members:Danilo  10.00 CHF\n\
+             \x20 Liabilities:Members:Danilo  -9.50 EUR\n\
+             \x20 Liabilities:Members:Bank  9.50 EUR\n\
+             \n"
+        );
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_csv() {
+        let (output, [deposit, purchase, exchange]) = export(ExportFormat::Csv);
+        let expected = format!(
+            "date,commit,description,account,amount,currency,class,ean\n\
+             2023-08-15,{deposit},Deposit,Liabilities:Members:danilo,-20.00,CHF,,\n\
+             2023-08-15,{deposit},Deposit,Assets:cash,20.00,CHF,,\n\
+             2023-08-15,{purchase},User danilo bought Vivi Kola,Income:Sales:fridge,-3.05,CHF,softdrink,7610867035003\n\
+             2023-08-15,{purchase},User danilo bought Vivi Kola,Liabilities:Members:danilo,3.05,CHF,softdrink,7610867035003\n\
+             2023-08-16,{exchange},Exchange,Liabilities:Members:bank,-10.00,CHF,,\n\
+             2023-08-16,{exchange},Exchange,Liabilities:Members:danilo,10.00,CHF,,\n\
+             2023-08-16,{exchange},Exchange,Liabilities:Members:danilo,-9.50,EUR,,\n\
+             2023-08-16,{exchange},Exchange,Liabilities:Members:bank,9.50,EUR,,\n"
+        );
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_multiline_description() {
+        let test_repo = TestRepo::new();
+        let data = format!("{}\ndescription = \"Vivi Kola\\nand Mate\"", DEPOSIT);
+        test_repo.commit_transaction_at("Deposit", &data, TIME);
+        let repo = Repo::open(test_repo.path()).unwrap();
+        let entries = entries(&repo).unwrap();
+        let config = ExportConfig::default();
+
+        let mut out = Vec::new();
+        write_ledger(&mut out, &entries, &config).unwrap();
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.starts_with("2023-08-15 * Vivi Kola and Mate\n    ; commit:"));
+
+        let mut out = Vec::new();
+        write_beancount(&mut out, &entries, &config).unwrap();
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains("2023-08-15 * \"Vivi Kola and Mate\"\n  commit:"));
+    }
+}
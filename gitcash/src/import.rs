@@ -0,0 +1,421 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use anyhow::Context;
+use libgitcash::{Account, Error, Repo, Transaction, TransactionMeta, TransactionOptions};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::report::{Cell, Format, Report};
+
+/// A row of the CSV file
+#[derive(Debug, Deserialize)]
+struct Row {
+    /// Explicit identity of the row, e.g. "2023-08-15-17"
+    #[serde(default)]
+    id: Option<String>,
+    from: String,
+    to: String,
+    /// Decimal amount, e.g. "2.50"
+    amount: String,
+    #[serde(default)]
+    currency: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    class: Option<String>,
+    #[serde(default)]
+    ean: Option<u64>,
+}
+
+/// A validated row
+struct ImportRow {
+    line: u64,
+    transaction: Transaction,
+    /// Whether the row was imported before
+    duplicate: bool,
+}
+
+/// Convert a row into a transaction (without import hash)
+///
+/// The transaction is checked like the ones of the transaction subcommands,
+/// except for credit limits, which are checked for all rows at once.
+fn parse_row(repo: &Repo, row: Row) -> Result<Transaction, Error> {
+    let from = Account::try_from(row.from)?;
+    let to = Account::try_from(row.to)?;
+    let amount = match &row.currency {
+        Some(code) => repo.parse_amount_in(&row.amount, code)?,
+        None => repo.parse_amount(&row.amount)?,
+    };
+    let transaction = Transaction {
+        from,
+        to,
+        amount: amount.minor_units(),
+        currency: row.currency,
+        description: row.description,
+        meta: Some(TransactionMeta {
+            class: row.class,
+            ean: row.ean,
+            import_hash: None,
+        }),
+        exchange: None,
+    };
+    let options = TransactionOptions {
+        allow_overdraft: true,
+    };
+    repo.check_transaction(&transaction, &options)?;
+    Ok(transaction)
+}
+
+/// Hash the identity of a row
+///
+/// Rows with an `id` are identified by it alone. Other rows are identified by
+/// their content, identical rows (e.g. two people buying the same drink) by
+/// their occurrence in the file. This way, importing a file again, even after
+/// renaming or copying it, detects all rows as duplicates. With a `file_name`,
+/// the rows only count as duplicates of the rows of a file with that name,
+/// e.g. for the same purchases on the sheet of another day.
+fn row_hash(
+    repo: &Repo,
+    file_name: Option<&str>,
+    id: Option<&str>,
+    transaction: &Transaction,
+    occurrence: usize,
+) -> String {
+    let content = match id {
+        Some(id) => vec!["id".to_string(), id.to_string()],
+        None => {
+            let meta = transaction.meta.as_ref();
+            let mut content = file_name
+                .map(str::to_string)
+                .into_iter()
+                .collect::<Vec<_>>();
+            content.extend([
+                transaction.from.to_string(),
+                transaction.to.to_string(),
+                transaction.amount.to_string(),
+                transaction
+                    .currency
+                    .clone()
+                    .unwrap_or_else(|| repo.config().currency.code.clone()),
+                transaction.description.clone().unwrap_or_default(),
+                meta.and_then(|meta| meta.class.clone()).unwrap_or_default(),
+                meta.and_then(|meta| meta.ean)
+                    .map(|ean| ean.to_string())
+                    .unwrap_or_default(),
+                occurrence.to_string(),
+            ]);
+            content
+        }
+    };
+    format!("{:x}", Sha256::digest(content.join("\n")))
+}
+
+/// Read and validate all rows of a CSV file
+///
+/// Fails if any row is invalid or if the new rows together would exceed a
+/// credit limit (unless overdrafts are allowed), after logging all invalid
+/// rows. With `per_file`, rows without an `id` are identified by the file name
+/// as well.
+fn read_rows(
+    repo: &Repo,
+    path: &Path,
+    per_file: bool,
+    options: &TransactionOptions,
+) -> anyhow::Result<Vec<ImportRow>> {
+    let mut reader = csv::Reader::from_path(path).context(format!("Could not open {:?}", path))?;
+    let headers = reader.headers()?.clone();
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let file_name = Some(file_name.as_ref()).filter(|_| per_file);
+    let imported = repo
+        .transactions()
+        .iter()
+        .filter_map(|record| record.transaction.meta.as_ref()?.import_hash.as_deref())
+        .collect::<HashSet<_>>();
+
+    let mut rows = Vec::new();
+    let mut ids = HashSet::new();
+    let mut occurrences = HashMap::new();
+    let mut invalid = 0;
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) if e.is_io_error() => {
+                return Err(e).context(format!("Could not read {:?}", path));
+            }
+            Err(e) => {
+                warn!("{}", e);
+                invalid += 1;
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line());
+        let parsed = record
+            .deserialize::<Row>(Some(&headers))
+            .map_err(|e| Error::TransactionParseError(e.to_string()))
+            .and_then(|row| match row.id.clone() {
+                Some(id) if !ids.insert(id.clone()) => {
+                    Err(Error::ValidationError(format!("Duplicate id: {}", id)))
+                }
+                id => Ok((id, parse_row(repo, row)?)),
+            });
+        let (id, mut transaction) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                warn!("Line {}: {}", line, e);
+                invalid += 1;
+                continue;
+            }
+        };
+        let occurrence = occurrences
+            .entry(row_hash(repo, None, None, &transaction, 0))
+            .and_modify(|count| *count += 1)
+            .or_insert(0);
+        let hash = row_hash(repo, file_name, id.as_deref(), &transaction, *occurrence);
+        let duplicate = imported.contains(hash.as_str());
+        if let Some(meta) = &mut transaction.meta {
+            meta.import_hash = Some(hash);
+        }
+        rows.push(ImportRow {
+            line,
+            transaction,
+            duplicate,
+        });
+    }
+    if !options.allow_overdraft {
+        let mut pending = Vec::new();
+        for row in rows.iter().filter(|row| !row.duplicate) {
+            match repo.check_limits_after(&pending, &row.transaction) {
+                Ok(()) => pending.push(&row.transaction),
+                Err(e) => {
+                    warn!("Line {}: {}", row.line, e);
+                    invalid += 1;
+                }
+            }
+        }
+    }
+    if invalid > 0 {
+        return Err(Error::ValidationError(format!(
+            "{} invalid row(s) in {:?}, nothing was imported",
+            invalid, path
+        ))
+        .into());
+    }
+    Ok(rows)
+}
+
+/// Import transactions from a CSV file, one commit per new row
+///
+/// The file needs a header row with the columns `from`, `to` and `amount`,
+/// and optionally `id`, `currency`, `description`, `class` and `ean`. Rows
+/// that were imported before (see [`row_hash`]) are skipped. All rows are
+/// validated, including credit limits, before the first one is committed (or
+/// the preview of a dry run is shown).
+pub fn import(
+    repo: &mut Repo,
+    path: &Path,
+    format: Format,
+    dry_run: bool,
+    per_file: bool,
+    options: TransactionOptions,
+) -> anyhow::Result<()> {
+    let rows = read_rows(repo, path, per_file, &options)?;
+
+    let mut report = Report::new(
+        format!("Import of {:?}", path),
+        &["line", "status", "from", "to", "amount", "description"],
+    );
+    report.empty_message = "No rows";
+    for row in &rows {
+        let transaction = &row.transaction;
+        let currency = repo
+            .config()
+            .find_currency(transaction.currency.as_deref())
+            .expect("Currency was validated");
+        report.rows.push(vec![
+            Cell::Number(row.line as i64),
+            if row.duplicate { "duplicate" } else { "new" }.into(),
+            transaction.from.to_string().into(),
+            transaction.to.to_string().into(),
+            Cell::Amount(transaction.amount.into(), currency.clone()),
+            transaction
+                .description
+                .as_deref()
+                .unwrap_or_default()
+                .into(),
+        ]);
+    }
+    report.print(format)?;
+
+    let duplicates = rows.iter().filter(|row| row.duplicate).count();
+    if dry_run {
+        println!(
+            "Dry run: {} new transaction(s), {} duplicate(s)",
+            rows.len() - duplicates,
+            duplicates
+        );
+        return Ok(());
+    }
+    let mut imported = 0;
+    for row in rows.into_iter().filter(|row| !row.duplicate) {
+        repo.create_transaction_with_options(row.transaction, options.clone())
+            .context(format!(
+                "Could not import line {} ({} transaction(s) were imported before)",
+                row.line, imported
+            ))?;
+        imported += 1;
+    }
+    println!(
+        "✅ Imported {} transaction(s), skipped {} duplicate(s)",
+        imported, duplicates
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use libgitcash::Repo;
+
+    use super::*;
+    use crate::test_utils::{TestRepo, DEPOSIT, REPO_CONFIG};
+
+    const SHEET: &str = "from,to,amount,description,class,ean\n\
+                         user:danilo,pos:fridge,2.50,Vivi Kola,softdrink,7610867035003\n\
+                         user:danilo,pos:fridge,2.50,Vivi Kola,softdrink,7610867035003\n";
+
+    /// Write a CSV file into the working directory and import it
+    fn import_file(
+        test_repo: &TestRepo,
+        repo: &mut Repo,
+        name: &str,
+        content: &str,
+        dry_run: bool,
+    ) -> anyhow::Result<()> {
+        let path = test_repo.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        import(
+            repo,
+            &path,
+            Format::Table,
+            dry_run,
+            false,
+            TransactionOptions::default(),
+        )
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+
+        // Identical rows are all imported, but only once
+        import_file(&test_repo, &mut repo, "2023-08-15.csv", SHEET, false).unwrap();
+        assert_eq!(repo.transactions().len(), 3);
+        import_file(&test_repo, &mut repo, "2023-08-15.csv", SHEET, false).unwrap();
+        assert_eq!(repo.transactions().len(), 3);
+
+        // Renaming the file does not matter
+        import_file(&test_repo, &mut repo, "renamed.csv", SHEET, false).unwrap();
+        assert_eq!(repo.transactions().len(), 3);
+
+        // With one more row, only that one is new
+        let sheet = format!(
+            "{}user:danilo,pos:fridge,2.50,Vivi Kola,softdrink,\n",
+            SHEET
+        );
+        import_file(&test_repo, &mut repo, "renamed.csv", &sheet, false).unwrap();
+        assert_eq!(repo.transactions().len(), 4);
+
+        // Rows with an id are identified by it, regardless of the file
+        let sheet = "id,from,to,amount\n17,user:danilo,pos:fridge,1.00\n";
+        import_file(&test_repo, &mut repo, "a.csv", sheet, false).unwrap();
+        import_file(&test_repo, &mut repo, "b.csv", sheet, false).unwrap();
+        assert_eq!(repo.transactions().len(), 5);
+    }
+
+    #[test]
+    fn test_per_file() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+
+        // The same purchases on the sheet of another day are new
+        for (name, transactions) in [
+            ("2023-08-15.csv", 3),
+            ("2023-08-16.csv", 5),
+            ("2023-08-16.csv", 5),
+        ] {
+            let path = test_repo.path().join(name);
+            std::fs::write(&path, SHEET).unwrap();
+            let options = TransactionOptions::default();
+            import(&mut repo, &path, Format::Table, false, true, options).unwrap();
+            assert_eq!(repo.transactions().len(), transactions);
+        }
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        let config = format!("{}\n[limits]\nuser = 0\n", REPO_CONFIG);
+        std::fs::write(test_repo.path().join("gitcash.toml"), config).unwrap();
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+
+        import_file(&test_repo, &mut repo, "sheet.csv", SHEET, true).unwrap();
+        assert_eq!(repo.transactions().len(), 1);
+
+        // Every row alone is within the limit, both together are not
+        let sheet = "from,to,amount\nuser:danilo,pos:fridge,15\nuser:danilo,pos:fridge,10\n";
+        assert!(import_file(&test_repo, &mut repo, "limit.csv", sheet, true).is_err());
+        assert!(import_file(&test_repo, &mut repo, "limit.csv", sheet, false).is_err());
+        assert_eq!(repo.transactions().len(), 1);
+
+        let path = test_repo.path().join("limit.csv");
+        let options = TransactionOptions {
+            allow_overdraft: true,
+        };
+        import(&mut repo, &path, Format::Table, false, false, options).unwrap();
+        assert_eq!(repo.transactions().len(), 3);
+    }
+
+    #[test]
+    fn test_malformed_rows() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+
+        for row in [
+            "user:danilo,pos:fridge,abc,",
+            "user:danilo,pos:fridge,-1.00,",
+            "user:danilo,pos:fridge,1.005,",
+            "user:nobody,pos:fridge,1.00,",
+            "pos:fridge,user:danilo,1.00,",
+            "user:danilo,pos:fridge,1.00,7610867035004",
+            "user:danilo,pos:fridge",
+        ] {
+            let sheet = format!(
+                "from,to,amount,ean\nuser:danilo,pos:fridge,1.00,\n{}\n",
+                row
+            );
+            let err = import_file(&test_repo, &mut repo, "sheet.csv", &sheet, true).unwrap_err();
+            assert!(
+                matches!(err.downcast_ref(), Some(Error::ValidationError(_))),
+                "{}: {}",
+                row,
+                err
+            );
+        }
+
+        let sheet =
+            "id,from,to,amount\n1,user:danilo,pos:fridge,1.00\n1,user:danilo,pos:fridge,2.00\n";
+        assert!(import_file(&test_repo, &mut repo, "sheet.csv", sheet, false).is_err());
+        assert_eq!(repo.transactions().len(), 1);
+    }
+}
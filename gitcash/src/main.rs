@@ -1,16 +1,40 @@
-use std::{fs::write, path::PathBuf};
+use std::{
+    fs::write,
+    path::PathBuf,
+    process::ExitCode,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{anyhow, bail, Context};
+use chrono::NaiveDate;
 use clap::{Parser, Subcommand};
 use config::Config;
 use inquire::{Autocomplete, InquireError};
-use libgitcash::{Account, AccountType, Repo, Transaction};
-use tracing::metadata::LevelFilter;
+use libgitcash::{
+    Account, AccountInfo, AccountType, Integration, OpenOptions, Product, Repo, Transaction,
+    TransactionMeta, TransactionOptions, TransactionRecord,
+};
+use tracing::{metadata::LevelFilter, warn};
 
-use crate::validators::{NewUsernameValidator, UsernameValidator};
+use crate::{
+    export::ExportFormat,
+    report::{Cell, Format, Report, SortBy},
+    statistics::{statistics_report, GroupBy, Kind},
+    transactions::{parse_account, TransactionArgs},
+    validators::{NewUsernameValidator, UsernameValidator},
+};
 
+mod accounts;
 mod config;
+mod export;
+mod import;
+mod report;
+mod statistics;
+#[cfg(test)]
+mod test_utils;
+mod transactions;
 mod validators;
+mod watch;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -18,6 +42,10 @@ struct Args {
     #[arg(short, long, default_value = "config.toml")]
     config: PathBuf,
 
+    /// Output format of reports [default: table]
+    #[arg(long, global = true, value_enum)]
+    format: Option<Format>,
+
     #[command(subcommand)]
     command: Command,
 }
@@ -27,9 +55,196 @@ enum Command {
     /// List all accounts
     Accounts,
     /// List all account balances
-    Balances,
+    Balances {
+        /// Sort order
+        #[arg(long, value_enum, default_value_t)]
+        sort: SortBy,
+        /// Only show balances in this currency, e.g. "EUR"
+        #[arg(long)]
+        currency: Option<String>,
+    },
     /// List all user accounts with negative balances
-    Shame,
+    Shame {
+        /// Sort order
+        #[arg(long, value_enum, default_value_t)]
+        sort: SortBy,
+        /// Only show balances in this currency, e.g. "EUR"
+        #[arg(long)]
+        currency: Option<String>,
+    },
+    /// List all transactions of an account
+    History {
+        /// The account, e.g. "user:danilo"
+        account: String,
+    },
+
+    /// Aggregate sales, deposits and consumption over time
+    Report {
+        /// What to aggregate
+        #[arg(value_enum)]
+        kind: Kind,
+        /// Length of the periods
+        #[arg(long, value_enum, default_value_t)]
+        by: GroupBy,
+        /// First day to include, e.g. "2023-08-01"
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// Last day to include, e.g. "2023-08-31"
+        #[arg(long)]
+        until: Option<NaiveDate>,
+        /// Only show the first N entries of every period
+        #[arg(long)]
+        top: Option<usize>,
+    },
+
+    /// Export all transactions as journal
+    Export {
+        /// Journal format (instead of the report format)
+        #[arg(long, value_enum, default_value_t)]
+        journal: ExportFormat,
+    },
+
+    /// Fetch new transactions from the remote, merge and push local ones
+    Sync,
+
+    /// Compare the transaction cache against a full rebuild
+    VerifyCache,
+
+    /// Watch for new transactions and send events to the configured sinks
+    Watch {
+        /// Check only once instead of continuously
+        #[arg(long)]
+        once: bool,
+    },
+
+    /// Add a product to the catalog
+    AddProduct {
+        /// The EAN code
+        #[arg(long)]
+        ean: u64,
+        /// The product name
+        #[arg(long)]
+        name: String,
+        /// The price, e.g. "2.50"
+        #[arg(long)]
+        price: String,
+        /// The product class, e.g. "softdrink"
+        #[arg(long)]
+        class: Option<String>,
+        /// Stock level at or below which the product is running low
+        #[arg(long)]
+        min_stock: Option<u32>,
+    },
+    /// Show the stock of all products
+    Inventory,
+    /// Record that products were added to the stock
+    Restock {
+        /// The EAN code
+        #[arg(long)]
+        ean: u64,
+        /// The number of items (negative to remove items, e.g. broken ones)
+        #[arg(long, allow_hyphen_values = true)]
+        quantity: i32,
+    },
+
+    /// A user pays a point of sale
+    Pay {
+        /// The paying user, e.g. "danilo"
+        #[arg(long, value_parser = parse_account)]
+        from: Account,
+        /// The point of sale (defaults to the account in the config)
+        #[arg(long, value_parser = parse_account)]
+        to: Option<Account>,
+        #[command(flatten)]
+        details: TransactionArgs,
+    },
+    /// Deposit money into a user account
+    Deposit {
+        /// The source of the money
+        #[arg(long, value_parser = parse_account, default_value = "source:cash")]
+        from: Account,
+        /// The receiving user, e.g. "danilo"
+        #[arg(long, value_parser = parse_account)]
+        to: Account,
+        #[command(flatten)]
+        details: TransactionArgs,
+    },
+    /// Transfer money from one user to another
+    Transfer {
+        /// The paying user, e.g. "danilo"
+        #[arg(long, value_parser = parse_account)]
+        from: Account,
+        /// The receiving user, e.g. "rnestler"
+        #[arg(long, value_parser = parse_account)]
+        to: Account,
+        #[command(flatten)]
+        details: TransactionArgs,
+    },
+    /// Exchange money between two users at the specified rate, e.g. CHF
+    /// into EUR
+    Exchange {
+        /// The user selling the amount, e.g. "danilo"
+        #[arg(long, value_parser = parse_account)]
+        from: Account,
+        /// The user paying back the exchanged amount, e.g. "bank"
+        #[arg(long, value_parser = parse_account)]
+        to: Account,
+        /// The currency the amount is exchanged into, e.g. "EUR"
+        #[arg(long)]
+        into: String,
+        /// Units of the target currency per unit of the amount's currency,
+        /// e.g. "0.95"
+        #[arg(long)]
+        rate: String,
+        #[command(flatten)]
+        details: TransactionArgs,
+    },
+    /// Add a new user
+    #[command(name = "adduser")]
+    AddUser {
+        /// The user name
+        #[arg(long)]
+        name: String,
+        /// Human-readable name stored in the account registry, e.g. "Danilo
+        /// Bargen"
+        #[arg(long)]
+        display_name: Option<String>,
+        /// Contact information stored in the account registry, e.g. an
+        /// e-mail address
+        #[arg(long)]
+        contact: Option<String>,
+        /// Print the commit message instead of committing the transaction
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Edit or archive an account
+    Account {
+        #[command(subcommand)]
+        command: AccountCommand,
+    },
+
+    /// Import transactions from a CSV file (columns: from, to, amount and
+    /// optionally id, currency, description, class, ean)
+    ///
+    /// Rows that were imported before are skipped. Rows with an id are
+    /// identified by it, other rows by their content and their occurrence in
+    /// the file, so renaming or copying a file does not import it twice.
+    Import {
+        /// Path to the CSV file
+        file: PathBuf,
+        /// Allow accounts to go below their credit limit
+        #[arg(long)]
+        allow_overdraft: bool,
+        /// Only show a preview of the imported transactions
+        #[arg(long)]
+        dry_run: bool,
+        /// Identify rows without an id by the file name as well, so that
+        /// the same rows in a file with another name (e.g. the sheet of
+        /// another day) are imported again
+        #[arg(long)]
+        per_file: bool,
+    },
 
     /// Interactive CLI
     Cli,
@@ -38,6 +253,34 @@ enum Command {
     GenerateConfig,
 }
 
+#[derive(Subcommand, PartialEq, Eq)]
+enum AccountCommand {
+    /// Change the display name or contact of an account
+    Edit {
+        /// The account, e.g. "danilo"
+        #[arg(long, value_parser = parse_account)]
+        account: Account,
+        /// New display name (an empty value removes it)
+        #[arg(long)]
+        display_name: Option<String>,
+        /// New contact information (an empty value removes it)
+        #[arg(long)]
+        contact: Option<String>,
+    },
+    /// Archive an account, e.g. of a member who left
+    Archive {
+        /// The account, e.g. "danilo"
+        #[arg(long, value_parser = parse_account)]
+        account: Account,
+    },
+    /// Restore an archived account
+    Unarchive {
+        /// The account, e.g. "danilo"
+        #[arg(long, value_parser = parse_account)]
+        account: Account,
+    },
+}
+
 #[derive(Clone)]
 struct CommandSuggester {
     commands: Vec<&'static str>,
@@ -79,6 +322,10 @@ impl Autocomplete for CommandSuggester {
 #[derive(Debug, Clone, Copy)]
 enum CliCommand {
     AddUser,
+    Deposit,
+    Transfer,
+    Balance,
+    Undo,
     Help,
 }
 
@@ -86,6 +333,10 @@ impl CliCommand {
     fn command(&self) -> &'static str {
         match self {
             CliCommand::AddUser => "adduser",
+            CliCommand::Deposit => "deposit",
+            CliCommand::Transfer => "transfer",
+            CliCommand::Balance => "balance",
+            CliCommand::Undo => "undo",
             CliCommand::Help => "help",
         }
     }
@@ -93,6 +344,10 @@ impl CliCommand {
     fn description(&self) -> &'static str {
         match self {
             CliCommand::AddUser => "Add a new user",
+            CliCommand::Deposit => "Deposit cash into your account",
+            CliCommand::Transfer => "Transfer money to another user",
+            CliCommand::Balance => "Show the balance of your account",
+            CliCommand::Undo => "Undo a recent purchase",
             CliCommand::Help => "Show this help",
         }
     }
@@ -103,16 +358,49 @@ impl TryFrom<&str> for CliCommand {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value.to_lowercase().as_ref() {
             "adduser" => Ok(CliCommand::AddUser),
+            "deposit" => Ok(CliCommand::Deposit),
+            "transfer" => Ok(CliCommand::Transfer),
+            "balance" => Ok(CliCommand::Balance),
+            "undo" => Ok(CliCommand::Undo),
             "help" => Ok(CliCommand::Help),
             other => Err(anyhow!("Invalid command: {}", other)),
         }
     }
 }
 
-pub fn main() -> anyhow::Result<()> {
+/// Exit code for invalid input (same as for invalid arguments)
+const EXIT_INVALID_INPUT: u8 = 2;
+
+pub fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+/// Return the exit code for an error: Transactions or arguments that were
+/// rejected by libgitcash count as invalid input, everything else is a
+/// general failure
+fn exit_code(error: &anyhow::Error) -> u8 {
+    match error.downcast_ref::<libgitcash::Error>() {
+        Some(
+            libgitcash::Error::ValidationError(_)
+            | libgitcash::Error::AmountParseError(_)
+            | libgitcash::Error::TransactionParseError(_)
+            | libgitcash::Error::CreditLimitError { .. },
+        ) => EXIT_INVALID_INPUT,
+        _ => 1,
+    }
+}
+
+fn run() -> anyhow::Result<()> {
     // Initialize logging subscriber
     let subscriber = tracing_subscriber::fmt()
         .with_max_level(LevelFilter::DEBUG)
+        .with_writer(std::io::stderr)
         .finish();
     tracing::subscriber::set_global_default(subscriber).expect("Could not set tracing subscriber");
 
@@ -143,47 +431,236 @@ pub fn main() -> anyhow::Result<()> {
     let config = Config::load(&args.config)?;
 
     // Open repo
-    let mut repo = Repo::open(&config.repo_path)?;
+    let options = OpenOptions {
+        cache: config.cache,
+        mode: config.load_mode,
+    };
+    let mut repo = Repo::open_with_options(&config.repo_path, options)?;
+    for diagnostic in repo.diagnostics() {
+        warn!("{}", diagnostic);
+    }
+    for violation in repo.violations() {
+        warn!("Invalid transaction in history: {}", violation);
+    }
 
     // Run command
+    let format = args.format.unwrap_or_default();
     match args.command {
         Command::Accounts => {
-            println!("Accounts:");
-            for account in repo.accounts() {
-                println!("- Account: {} ({:?})", account.name, account.account_type);
-            }
+            let mut accounts = repo.accounts().into_iter().collect::<Vec<_>>();
+            accounts.sort_by_key(|account| account.to_string());
+            let mut report = Report::new(
+                "Accounts",
+                &[
+                    "account",
+                    "type",
+                    "name",
+                    "display name",
+                    "contact",
+                    "archived",
+                ],
+            );
+            report.rows = accounts
+                .into_iter()
+                .map(|account| {
+                    let info = repo.account_info(&account);
+                    vec![
+                        account.to_string().into(),
+                        account.account_type.prefix().into(),
+                        account.name.clone().into(),
+                        info.and_then(|i| i.display_name.clone())
+                            .unwrap_or_default()
+                            .into(),
+                        info.and_then(|i| i.contact.clone())
+                            .unwrap_or_default()
+                            .into(),
+                        if info.is_some_and(|i| i.archived) {
+                            "yes".into()
+                        } else {
+                            "no".into()
+                        },
+                    ]
+                })
+                .collect();
+            report.print(format)?;
         }
-        Command::Balances => {
-            println!("Balances:");
-            for (account, balance) in repo.balances() {
-                println!(
-                    "- {}: {:.2} CHF [{:?}]",
-                    account.name,
-                    balance as f32 / 100.0,
-                    account.account_type
-                );
-            }
+        Command::Balances { sort, currency } => {
+            let report = balance_report("Balances", &repo, currency, sort, |_, _| true)?;
+            report.print(format)?;
         }
-        Command::Shame => {
-            println!("Wall of shame (negative user balances):");
-            let negative_balance_accounts = repo
-                .balances()
+        Command::Shame { sort, currency } => {
+            let mut report = balance_report(
+                "Wall of shame (negative user balances)",
+                &repo,
+                currency,
+                sort,
+                |account, balance| account.account_type == AccountType::User && balance < 0,
+            )?;
+            report.empty_message = "None at all! 🎉";
+            report.print(format)?;
+        }
+        Command::History { account } => {
+            let account = Account::try_from(account)?;
+            let mut report = Report::new(
+                format!("History of {}", account),
+                &[
+                    "date",
+                    "commit",
+                    "counterparty",
+                    "description",
+                    "currency",
+                    "amount",
+                    "balance",
+                ],
+            );
+            report.empty_message = "No transactions";
+            report.rows = repo
+                .history(&account)?
                 .into_iter()
-                .filter(|(account, balance)| {
-                    account.account_type == AccountType::User && *balance < 0
+                .map(|entry| {
+                    let record = entry.record;
+                    let description = record
+                        .transaction
+                        .description
+                        .as_deref()
+                        .unwrap_or(&record.title);
+                    vec![
+                        record.time.format("%Y-%m-%d %H:%M").to_string().into(),
+                        record.id.to_string().into(),
+                        entry.counterparty.to_string().into(),
+                        description.into(),
+                        entry.currency.code.as_str().into(),
+                        Cell::Amount(entry.amount, entry.currency.clone()),
+                        Cell::Amount(entry.balance, entry.currency.clone()),
+                    ]
                 })
-                .collect::<Vec<_>>();
-            for (account, balance) in &negative_balance_accounts {
-                println!(
-                    "- {}: {:.2} CHF [{:?}]",
-                    account.name,
-                    *balance as f32 / 100.0,
-                    account.account_type
-                );
+                .collect();
+            report.print(format)?;
+        }
+        Command::Report {
+            kind,
+            by,
+            since,
+            until,
+            top,
+        } => {
+            let report = statistics_report(&repo, kind, by, since, until, top)?;
+            report.print(format)?;
+        }
+        Command::Export { journal } => {
+            if args.format.is_some() {
+                return Err(libgitcash::Error::ValidationError(
+                    "Use --journal to choose the format of exports".into(),
+                )
+                .into());
             }
-            if negative_balance_accounts.is_empty() {
-                println!("None at all! 🎉");
+            export::export(&repo, &config.export, journal)?;
+        }
+        Command::Sync => {
+            let result = repo.sync(&config.remote)?;
+            match result.integration {
+                Integration::UpToDate => println!("No new commits on {}", config.remote),
+                Integration::FastForward => println!("Fast-forwarded to {}", config.remote),
+                Integration::Merge => println!("Merged changes from {}", config.remote),
+            }
+            println!("Received {} new transaction(s)", result.new_transactions);
+            if result.pushed {
+                println!("Pushed local changes to {}", config.remote);
+            }
+            println!("✅ Synced with {}", config.remote);
+        }
+        Command::VerifyCache => {
+            let head = repo.verify_cache()?;
+            println!("✅ Cache up to commit {} is valid", head);
+        }
+        Command::Watch { once } => {
+            watch::watch(&mut repo, &config, once)?;
+        }
+        Command::AddProduct {
+            ean,
+            name,
+            price,
+            class,
+            min_stock,
+        } => {
+            let price = repo.parse_amount(&price)?;
+            if price.minor_units() < 0 {
+                bail!("Price may not be negative");
             }
+            repo.add_product(Product {
+                ean,
+                name: name.clone(),
+                price: price.minor_units(),
+                class,
+                min_stock,
+            })?;
+            println!("✅ Added product {} ({})", name, ean);
+        }
+        Command::Inventory => {
+            let mut report = Report::new("Inventory", &["ean", "name", "stock", "low"]);
+            report.empty_message = "No products";
+            report.rows = repo
+                .inventory()
+                .into_iter()
+                .map(|level| {
+                    vec![
+                        level.ean.to_string().into(),
+                        level.name.as_deref().unwrap_or("Unknown product").into(),
+                        Cell::Number(level.stock()),
+                        if level.is_low() { "yes" } else { "no" }.into(),
+                    ]
+                })
+                .collect();
+            report.print(format)?;
+        }
+        Command::Restock { ean, quantity } => {
+            repo.restock(ean, quantity)?;
+            println!("✅ Restocked {} item(s) of {}", quantity, ean);
+        }
+        Command::Pay { from, to, details } => {
+            let to = to.unwrap_or_else(|| config.account.clone());
+            transactions::pay(&mut repo, from, to, details)?;
+        }
+        Command::Deposit { from, to, details } => {
+            transactions::deposit(&mut repo, from, to, details)?;
+        }
+        Command::Transfer { from, to, details } => {
+            transactions::transfer(&mut repo, from, to, details)?;
+        }
+        Command::Exchange {
+            from,
+            to,
+            into,
+            rate,
+            details,
+        } => {
+            transactions::exchange(&mut repo, from, to, &into, &rate, details)?;
+        }
+        Command::AddUser {
+            name,
+            display_name,
+            contact,
+            dry_run,
+        } => {
+            transactions::add_user(&mut repo, &name, display_name, contact, dry_run)?;
+        }
+        Command::Account { command } => match command {
+            AccountCommand::Edit {
+                account,
+                display_name,
+                contact,
+            } => accounts::edit(&mut repo, &account, display_name, contact)?,
+            AccountCommand::Archive { account } => accounts::archive(&mut repo, &account, true)?,
+            AccountCommand::Unarchive { account } => accounts::archive(&mut repo, &account, false)?,
+        },
+        Command::Import {
+            file,
+            allow_overdraft,
+            dry_run,
+            per_file,
+        } => {
+            let options = TransactionOptions { allow_overdraft };
+            import::import(&mut repo, &file, format, dry_run, per_file, options)?;
         }
         Command::Cli => {
             println!("Welcome to the GitCash CLI for {}!", config.git_name);
@@ -204,8 +681,71 @@ pub fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Build a report of the balances of all accounts matching `filter`, in all
+/// currencies or only in the specified one
+fn balance_report(
+    title: &str,
+    repo: &Repo,
+    currency: Option<String>,
+    sort: SortBy,
+    filter: impl Fn(&Account, i64) -> bool,
+) -> anyhow::Result<Report> {
+    let currencies = repo
+        .config()
+        .currencies()
+        .filter(|c| currency.as_ref().map_or(true, |code| &c.code == code))
+        .collect::<Vec<_>>();
+    if let (Some(code), true) = (&currency, currencies.is_empty()) {
+        bail!("Unknown currency: {}", code);
+    }
+
+    // Sort by account and currency (primary first), then by balance if requested
+    let balances = repo.balances()?;
+    let mut entries = currencies
+        .iter()
+        .enumerate()
+        .flat_map(|(index, currency)| {
+            balances
+                .get(&currency.code)
+                .into_iter()
+                .flatten()
+                .filter(|(account, balance)| filter(account, **balance))
+                .map(move |(account, balance)| (account.to_string(), index, *balance, *currency))
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+    if sort == SortBy::Balance {
+        entries.sort_by_key(|(_, _, balance, _)| *balance);
+    }
+
+    let mut report = Report::new(title, &["account", "currency", "balance"]);
+    report.rows = entries
+        .into_iter()
+        .map(|(account, _, balance, currency)| {
+            vec![
+                account.into(),
+                currency.code.as_str().into(),
+                Cell::Amount(balance, currency.clone()),
+            ]
+        })
+        .collect();
+    Ok(report)
+}
+
 // Valid commands
-const COMMANDS: [CliCommand; 2] = [CliCommand::AddUser, CliCommand::Help];
+const COMMANDS: [CliCommand; 6] = [
+    CliCommand::AddUser,
+    CliCommand::Deposit,
+    CliCommand::Transfer,
+    CliCommand::Balance,
+    CliCommand::Undo,
+    CliCommand::Help,
+];
+
+/// Scanned barcodes are all digits, and at least as long as an EAN-8
+fn is_ean(input: &str) -> bool {
+    input.len() >= 8 && input.bytes().all(|b| b.is_ascii_digit())
+}
 
 fn handle_cli_input(repo: &mut Repo, config: &Config) -> anyhow::Result<()> {
     // Get list of valid user account names
@@ -216,22 +756,45 @@ fn handle_cli_input(repo: &mut Repo, config: &Config) -> anyhow::Result<()> {
         .map(|acc| acc.name)
         .collect::<Vec<_>>();
 
-    // Autocompletion: All names that contain the current input as
-    // substring (case-insensitive)
+    // Autocompletion: All names of non-archived users where the name or the
+    // display name contains the current input as substring (case-insensitive)
     let name_suggester = {
-        let usernames = usernames.clone();
+        let candidates = repo
+            .accounts()
+            .into_iter()
+            .filter(|acc| acc.account_type == AccountType::User)
+            .filter_map(|acc| match repo.account_info(&acc) {
+                Some(info) if info.archived => None,
+                Some(info) => {
+                    let display_name = info.display_name.clone().unwrap_or_default();
+                    Some((acc.name, display_name))
+                }
+                None => Some((acc.name, String::new())),
+            })
+            .collect::<Vec<_>>();
         move |val: &str| {
-            Ok(usernames
+            let val = val.to_lowercase();
+            Ok(candidates
                 .iter()
-                .filter(|acc| acc.to_lowercase().contains(&val.to_lowercase()))
-                .cloned()
+                .filter(|(name, display_name)| {
+                    name.to_lowercase().contains(&val) || display_name.to_lowercase().contains(&val)
+                })
+                .map(|(name, _)| name.clone())
                 .collect::<Vec<_>>())
         }
     };
+    let prompt_name = |message: &str| {
+        inquire::Text::new(message)
+            .with_autocomplete(name_suggester.clone())
+            .with_validator(UsernameValidator::new(usernames.clone()))
+            .prompt()
+    };
 
     // First, ask for command, product or amount
+    let currency = &repo.config().currency;
+    let example_amount = currency.format_amount(currency.divisor as i64 * 5 / 2);
     let target = inquire::Text::new("Amount, EAN or command:")
-        .with_placeholder("e.g. 2.50 CHF")
+        .with_placeholder(&format!("e.g. {}", example_amount))
         .with_autocomplete(CommandSuggester::new(&COMMANDS))
         .prompt()?;
 
@@ -246,12 +809,84 @@ fn handle_cli_input(repo: &mut Repo, config: &Config) -> anyhow::Result<()> {
                 from: Account::source("cash")?,
                 to: Account::user(new_name.clone())?,
                 amount: 0,
+                currency: None,
                 description: Some(format!("Create user {}", new_name)),
                 meta: None,
+                exchange: None,
+            })?;
+            repo.set_account_info(AccountInfo {
+                created: accounts::today(),
+                ..AccountInfo::new(Account::user(new_name.clone())?)
             })?;
             println!("Successfully added user {}", new_name);
             return Ok(());
         }
+        Ok(CliCommand::Deposit) => {
+            let account = Account::user(prompt_name("Name:")?)?;
+            let amount = prompt_amount(repo, "Deposited amount:")?;
+            repo.create_transaction(Transaction {
+                from: Account::source("cash")?,
+                to: account.clone(),
+                amount,
+                currency: None,
+                description: Some("Cash deposit".into()),
+                meta: None,
+                exchange: None,
+            })?;
+            println!(
+                "✅ Deposited {}",
+                repo.config().currency.format_amount(amount.into())
+            );
+            print_balance(repo, &account)?;
+            return Ok(());
+        }
+        Ok(CliCommand::Transfer) => {
+            let from = Account::user(prompt_name("Your name:")?)?;
+            let to = Account::user(prompt_name("Recipient:")?)?;
+            if from == to {
+                bail!("Cannot transfer money to yourself");
+            }
+            let amount = prompt_amount(repo, "Amount:")?;
+            let description = inquire::Text::new("Description:")
+                .with_placeholder("optional")
+                .prompt()?;
+            let transaction = Transaction {
+                from: from.clone(),
+                to: to.clone(),
+                amount,
+                currency: None,
+                description: Some(description.trim().to_string()).filter(|d| !d.is_empty()),
+                meta: None,
+                exchange: None,
+            };
+            if !within_limits(repo, &transaction)? {
+                return Ok(());
+            }
+            let formatted_amount = repo.config().currency.format_amount(amount.into());
+            let confirmed = inquire::Confirm::new(&format!(
+                "Transfer {} from {} to {}?",
+                formatted_amount, from.name, to.name
+            ))
+            .with_default(false)
+            .prompt()?;
+            if !confirmed {
+                println!("Transfer cancelled");
+                return Ok(());
+            }
+            repo.create_transaction(transaction)?;
+            println!("✅ Transferred {} to {}", formatted_amount, to.name);
+            print_balance(repo, &from)?;
+            return Ok(());
+        }
+        Ok(CliCommand::Balance) => {
+            let account = Account::user(prompt_name("Name:")?)?;
+            print_balance(repo, &account)?;
+            return Ok(());
+        }
+        Ok(CliCommand::Undo) => {
+            undo(repo, config)?;
+            return Ok(());
+        }
         Ok(CliCommand::Help) => {
             println!("Available commands:");
             for command in COMMANDS {
@@ -262,25 +897,214 @@ fn handle_cli_input(repo: &mut Repo, config: &Config) -> anyhow::Result<()> {
         Err(_) => {}
     };
 
-    // Not a command, treat it as amount if below a reasonable limit
-    let amount: f32 = target
-        .parse()
-        .context(format!("Invalid amount: {}", target))?;
-    if amount > 1337.0 {
-        bail!("Neither a valid command nor a known EAN, and definitely not a reasonable amount either");
+    // Not a command, check whether it's a known EAN
+    let (amount, description, meta) = if is_ean(&target) {
+        let ean: u64 = target.parse()?;
+        let product = repo
+            .catalog()
+            .lookup(ean)
+            .ok_or_else(|| anyhow!("Unknown EAN: {}", ean))?;
+        println!("Product: {}", product.name);
+        (
+            product.price,
+            Some(product.name.clone()),
+            Some(TransactionMeta {
+                class: product.class.clone(),
+                ean: Some(ean),
+                import_hash: None,
+            }),
+        )
+    } else {
+        // Treat it as amount if below a reasonable limit
+        let amount = repo.parse_amount(&target)?;
+        if amount > repo.parse_amount("1337")? {
+            bail!("Neither a valid command nor a known EAN, and definitely not a reasonable amount either");
+        }
+        (amount.minor_units(), None, None)
+    };
+    let name = prompt_name("Name:")?;
+    let transaction = Transaction {
+        from: Account::user(name.clone())?,
+        to: config.account.clone(),
+        amount,
+        currency: None,
+        description,
+        meta,
+        exchange: None,
+    };
+    if !within_limits(repo, &transaction)? {
+        return Ok(());
     }
-    let name = inquire::Text::new("Name:")
-        .with_autocomplete(name_suggester.clone())
-        .with_validator(UsernameValidator::new(usernames))
+    let currency = &repo.config().currency;
+    println!(
+        "Creating transaction: {} pays {}",
+        name,
+        currency.format_amount(amount.into())
+    );
+    repo.create_transaction(transaction)?;
+
+    Ok(())
+}
+
+/// Check a transaction against the credit limits, and explain why it is
+/// refused if it would bring an account below its limit
+///
+/// Returns whether the transaction may be created.
+fn within_limits(repo: &Repo, transaction: &Transaction) -> anyhow::Result<bool> {
+    match repo.check_limits(transaction) {
+        Ok(()) => Ok(true),
+        Err(libgitcash::Error::CreditLimitError {
+            account,
+            balance,
+            limit,
+        }) => {
+            let currency = &repo.config().currency;
+            println!(
+                "❌ Refused: The balance of {} may not drop below {}",
+                account.name,
+                currency.format_amount(limit)
+            );
+            println!("Current balance: {}", currency.format_amount(balance));
+            println!("Please deposit some money first.");
+            Ok(false)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Let the user pick a recent purchase on this point of sale and revert it
+///
+/// Only purchases within the configured undo window that were not reverted
+/// yet are offered.
+fn undo(repo: &mut Repo, config: &Config) -> anyhow::Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let window = i64::try_from(config.undo_window * 60)?;
+    let candidates = repo
+        .transactions()
+        .iter()
+        .rev()
+        .filter(|record| now - record.time.timestamp() <= window)
+        .filter(|record| record.transaction.to == config.account && !record.is_reverted())
+        .map(|record| {
+            (
+                record.id,
+                record.transaction.from.clone(),
+                record.title.clone(),
+                undo_label(repo, record),
+            )
+        })
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        println!(
+            "No purchases in the last {} minute(s) that could be undone",
+            config.undo_window
+        );
+        return Ok(());
+    }
+
+    let options = candidates
+        .iter()
+        .map(|(_, _, _, label)| label.as_str())
+        .collect::<Vec<_>>();
+    let selected = inquire::Select::new("Purchase to undo:", options)
+        .raw_prompt()?
+        .index;
+    let (id, from, title, _) = &candidates[selected];
+    let confirmed = inquire::Confirm::new(&format!("Undo \"{}\"?", title))
+        .with_default(false)
         .prompt()?;
-    println!("Creating transaction: {} pays {:.2} CHF", name, amount);
-    repo.create_transaction(Transaction {
-        from: Account::user(name)?,
-        to: config.account.clone(),
-        amount: repo.convert_amount(amount),
-        description: None,
-        meta: None,
-    })?;
+    if !confirmed {
+        println!("Undo cancelled");
+        return Ok(());
+    }
+    repo.revert_transaction(*id)?;
+    println!("✅ Reverted \"{}\"", title);
+    if from.account_type == AccountType::User {
+        print_balance(repo, from)?;
+    }
+    Ok(())
+}
+
+/// Describe a purchase in the undo selection by time, amount, title and short
+/// commit ID, so that identical purchases can be told apart
+fn undo_label(repo: &Repo, record: &TransactionRecord) -> String {
+    let config = repo.config();
+    let currency = config
+        .find_currency(record.transaction.currency.as_deref())
+        .unwrap_or(&config.currency);
+    let id = record.id.to_string();
+    format!(
+        "{}  {}  {} ({})",
+        record.time.format("%H:%M"),
+        currency.format_amount(record.transaction.amount.into()),
+        record.title,
+        &id[..7]
+    )
+}
 
+/// Ask for a positive amount in the primary currency
+fn prompt_amount(repo: &Repo, message: &str) -> anyhow::Result<i32> {
+    let currency = &repo.config().currency;
+    let input = inquire::Text::new(message)
+        .with_placeholder(&format!(
+            "e.g. {}",
+            currency.format_amount(currency.divisor as i64 * 10)
+        ))
+        .prompt()?;
+    let amount = repo.parse_amount(&input)?;
+    if amount.minor_units() <= 0 {
+        bail!("Amount must be positive");
+    }
+    Ok(amount.minor_units())
+}
+
+/// Print the balance of an account in the primary currency, and in all other
+/// currencies the account holds money in
+fn print_balance(repo: &Repo, account: &Account) -> anyhow::Result<()> {
+    let balances = repo.balances()?;
+    let primary = &repo.config().currency;
+    for currency in repo.config().currencies() {
+        let balance = balances
+            .get(&currency.code)
+            .and_then(|balances| balances.get(account))
+            .copied();
+        if balance.is_some() || currency == primary {
+            println!(
+                "Balance of {}: {}",
+                account.name,
+                currency.format_amount(balance.unwrap_or_default())
+            );
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn verify_cli() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn test_export_format() {
+        let args = Args::try_parse_from(["gitcash", "export", "--journal", "beancount"]).unwrap();
+        assert!(args.format.is_none());
+        assert!(matches!(
+            args.command,
+            Command::Export {
+                journal: ExportFormat::Beancount
+            }
+        ));
+
+        // The report format is separate, and only accepts report formats
+        let args = Args::try_parse_from(["gitcash", "export", "--format", "csv"]).unwrap();
+        assert_eq!(args.format, Some(Format::Csv));
+        assert!(Args::try_parse_from(["gitcash", "export", "--format", "beancount"]).is_err());
+        assert!(Args::try_parse_from(["gitcash", "--format", "ledger", "export"]).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use std::io;
+
+use clap::ValueEnum;
+use libgitcash::Currency;
+use serde_json::{Map, Value};
+
+/// Output format of reports
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human-readable table
+    #[default]
+    Table,
+    /// JSON array of objects (amounts as integers, see currency divisor)
+    Json,
+    /// CSV with a header row (amounts as decimal numbers)
+    Csv,
+}
+
+/// Sort order of account reports
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortBy {
+    /// Alphabetically by account
+    #[default]
+    Name,
+    /// By balance (lowest first), then by account
+    Balance,
+}
+
+/// A single value in a report
+pub enum Cell {
+    Text(String),
+    Number(i64),
+    Amount(i64, Currency),
+}
+
+impl Cell {
+    fn to_text(&self, format: Format) -> String {
+        match self {
+            Cell::Text(text) => text.clone(),
+            Cell::Number(number) => number.to_string(),
+            Cell::Amount(amount, currency) if format == Format::Csv => {
+                currency.format_decimal(*amount)
+            }
+            Cell::Amount(amount, currency) => currency.format_amount(*amount),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Cell::Text(text) => Value::from(text.as_str()),
+            Cell::Number(number) | Cell::Amount(number, _) => Value::from(*number),
+        }
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Cell::Text(text)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        Cell::Text(text.to_string())
+    }
+}
+
+/// Tabular output of a command
+pub struct Report {
+    /// Title, only shown in table format
+    pub title: String,
+    /// Column names (used as keys in JSON)
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<Vec<Cell>>,
+    /// Message shown instead of an empty table
+    pub empty_message: &'static str,
+}
+
+impl Report {
+    pub fn new(title: impl Into<String>, columns: &[&'static str]) -> Self {
+        Self {
+            title: title.into(),
+            columns: columns.to_vec(),
+            rows: Vec::new(),
+            empty_message: "No entries",
+        }
+    }
+
+    /// Print the report to stdout
+    pub fn print(&self, format: Format) -> anyhow::Result<()> {
+        match format {
+            Format::Table => self.print_table(),
+            Format::Json => {
+                let objects = self
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        let object = self
+                            .columns
+                            .iter()
+                            .zip(row)
+                            .map(|(column, cell)| (column.to_string(), cell.to_json()))
+                            .collect::<Map<_, _>>();
+                        Value::Object(object)
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&objects)?);
+            }
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(io::stdout());
+                writer.write_record(&self.columns)?;
+                for row in &self.rows {
+                    writer.write_record(row.iter().map(|cell| cell.to_text(format)))?;
+                }
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn print_table(&self) {
+        println!("{}:", self.title);
+        if self.rows.is_empty() {
+            println!("{}", self.empty_message);
+            return;
+        }
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.to_text(Format::Table))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let widths = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                rows.iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([column.len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        let print_row = |cells: Vec<(&str, bool)>| {
+            let line = cells
+                .iter()
+                .zip(&widths)
+                .map(|((text, right_align), width)| match right_align {
+                    true => format!("{:>width$}", text, width = width),
+                    false => format!("{:<width$}", text, width = width),
+                })
+                .collect::<Vec<_>>()
+                .join("  ");
+            println!("{}", line.trim_end());
+        };
+        let right_aligned = |i: usize| {
+            self.rows
+                .first()
+                .is_some_and(|row| !matches!(row[i], Cell::Text(_)))
+        };
+        print_row(
+            self.columns
+                .iter()
+                .enumerate()
+                .map(|(i, column)| (*column, right_aligned(i)))
+                .collect(),
+        );
+        for row in &rows {
+            print_row(
+                row.iter()
+                    .enumerate()
+                    .map(|(i, text)| (text.as_str(), right_aligned(i)))
+                    .collect(),
+            );
+        }
+    }
+}
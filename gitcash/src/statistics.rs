@@ -0,0 +1,103 @@
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use libgitcash::{Aggregation, AggregationQuery, Period, Repo};
+
+use crate::report::{Cell, Report};
+
+/// What the statistics report aggregates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Kind {
+    /// Revenue per point of sale
+    Revenue,
+    /// Sales per product class
+    Classes,
+    /// Sales per product (EAN)
+    Products,
+    /// Deposits per source
+    Deposits,
+    /// Purchases per user, biggest consumers first
+    Consumers,
+}
+
+/// Length of the periods in the statistics report
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    Day,
+    Week,
+    #[default]
+    Month,
+}
+
+/// Build a report of transactions aggregated by period
+///
+/// With `top`, only the first rows of every period are included.
+pub fn statistics_report(
+    repo: &Repo,
+    kind: Kind,
+    group_by: GroupBy,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    top: Option<usize>,
+) -> anyhow::Result<Report> {
+    let (aggregation, title, key_column) = match kind {
+        Kind::Revenue => (Aggregation::Revenue, "Revenue", "account"),
+        Kind::Classes => (Aggregation::Class, "Sales per class", "class"),
+        Kind::Products => (Aggregation::Ean, "Sales per product", "ean"),
+        Kind::Deposits => (Aggregation::Deposits, "Deposits", "account"),
+        Kind::Consumers => (Aggregation::Consumers, "Top consumers", "account"),
+    };
+    let period = match group_by {
+        GroupBy::Day => Period::Day,
+        GroupBy::Week => Period::Week,
+        GroupBy::Month => Period::Month,
+    };
+    let rows = repo.aggregate(&AggregationQuery {
+        aggregation,
+        period,
+        since,
+        until,
+    });
+
+    let mut columns = vec!["period", key_column];
+    if kind == Kind::Products {
+        columns.push("name");
+    }
+    columns.extend(["count", "currency", "amount"]);
+    let mut report = Report::new(title, &columns);
+    report.empty_message = "No transactions";
+
+    let mut rank = 0;
+    for (index, row) in rows.iter().enumerate() {
+        rank = match index > 0 && rows[index - 1].period_start == row.period_start {
+            true => rank + 1,
+            false => 1,
+        };
+        if top.is_some_and(|top| rank > top) {
+            continue;
+        }
+        let currency = repo
+            .config()
+            .find_currency(Some(&row.currency))
+            .ok_or_else(|| anyhow::anyhow!("Unknown currency: {}", row.currency))?;
+        let mut cells: Vec<Cell> = vec![
+            period.label(row.period_start).into(),
+            row.key.as_str().into(),
+        ];
+        if kind == Kind::Products {
+            let name = row
+                .key
+                .parse()
+                .ok()
+                .and_then(|ean| repo.catalog().lookup(ean))
+                .map_or("Unknown product", |product| product.name.as_str());
+            cells.push(name.into());
+        }
+        cells.extend([
+            Cell::Number(row.count as i64),
+            row.currency.as_str().into(),
+            Cell::Amount(row.amount, currency.clone()),
+        ]);
+        report.rows.push(cells);
+    }
+    Ok(report)
+}
@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use git2::{Oid, Repository, Signature, Time};
+use tempfile::TempDir;
+
+use crate::config::Config;
+
+/// Repository config used in tests
+pub const REPO_CONFIG: &str = r#"name = "Test"
+
+[currency]
+code = "CHF"
+divisor = 100
+
+[[other_currency]]
+code = "EUR"
+divisor = 100
+"#;
+
+pub const DEPOSIT: &str = "from = \"source:cash\"\nto = \"user:danilo\"\namount = 2000";
+pub const PURCHASE: &str = "from = \"user:danilo\"\nto = \"pos:fridge\"\namount = 250";
+
+/// A temporary GitCash repository
+pub struct TestRepo {
+    pub dir: TempDir,
+    pub repo: Repository,
+}
+
+impl TestRepo {
+    /// Create a new repository containing a committed `gitcash.toml`
+    pub fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join("gitcash.toml"), REPO_CONFIG).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("gitcash.toml")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = Self::signature();
+        repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])
+            .unwrap();
+        drop(tree);
+        Self { dir, repo }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Return a client config for this repository, with the watch state
+    /// stored in the (untracked) `watch-state` file of the working directory
+    pub fn config(&self) -> Config {
+        let mut config: Config = toml::from_str(&format!(
+            "repo_path = {:?}\naccount = \"pos:fridge\"\ngit_name = \"Test\"\n\
+             git_email = \"test@example.com\"",
+            self.path()
+        ))
+        .unwrap();
+        config.watch.state_file = self.path().join("watch-state");
+        config
+    }
+
+    /// Create a transaction commit on top of HEAD
+    pub fn commit_transaction(&self, title: &str, data: &str) -> Oid {
+        self.commit_with_signature(title, data, Self::signature())
+    }
+
+    /// Create a transaction commit at the specified time (seconds since the
+    /// epoch, UTC)
+    pub fn commit_transaction_at(&self, title: &str, data: &str, time: i64) -> Oid {
+        let sig = Signature::new("Test", "test@example.com", &Time::new(time, 0)).unwrap();
+        self.commit_with_signature(title, data, sig)
+    }
+
+    fn commit_with_signature(&self, title: &str, data: &str, sig: Signature) -> Oid {
+        let message = format!("Transaction: {}\n\n---\n{}\n---", title, data);
+        let head = self.repo.head().unwrap().peel_to_commit().unwrap();
+        self.repo
+            .commit(
+                Some("HEAD"),
+                &sig,
+                &sig,
+                &message,
+                &head.tree().unwrap(),
+                &[&head],
+            )
+            .unwrap()
+    }
+
+    /// Create a commit on top of HEAD reverting the specified transaction
+    pub fn commit_revert(&self, title: &str, transaction: Oid) -> Oid {
+        let message = format!("Revert: {}\n\nThis reverts commit {}.", title, transaction);
+        let sig = Self::signature();
+        let head = self.repo.head().unwrap().peel_to_commit().unwrap();
+        self.repo
+            .commit(
+                Some("HEAD"),
+                &sig,
+                &sig,
+                &message,
+                &head.tree().unwrap(),
+                &[&head],
+            )
+            .unwrap()
+    }
+
+    fn signature() -> Signature<'static> {
+        Signature::now("Test", "test@example.com").unwrap()
+    }
+}
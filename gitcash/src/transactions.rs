@@ -0,0 +1,292 @@
+use clap::Args;
+use libgitcash::{
+    Account, AccountInfo, AccountType, Error, Exchange, Money, Repo, Transaction, TransactionMeta,
+    TransactionOptions,
+};
+
+use crate::accounts::today;
+
+/// Amount, description and meta of a transaction created by a subcommand
+#[derive(Args, Debug, PartialEq, Eq)]
+pub struct TransactionArgs {
+    /// The amount, e.g. "2.50"
+    #[arg(long)]
+    amount: String,
+    /// Currency of the amount, e.g. "EUR" (defaults to the primary currency)
+    #[arg(long)]
+    currency: Option<String>,
+    /// Description of the transaction
+    #[arg(long)]
+    description: Option<String>,
+    /// Product class, e.g. "softdrink"
+    #[arg(long)]
+    class: Option<String>,
+    /// EAN code of the product
+    #[arg(long)]
+    ean: Option<u64>,
+    /// Allow the paying account to go below its credit limit
+    #[arg(long)]
+    allow_overdraft: bool,
+    /// Print the commit message instead of committing the transaction
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl TransactionArgs {
+    fn options(&self) -> TransactionOptions {
+        TransactionOptions {
+            allow_overdraft: self.allow_overdraft,
+        }
+    }
+}
+
+/// Parse an account identifier, a bare name refers to a user account
+pub fn parse_account(value: &str) -> Result<Account, Error> {
+    match value.contains(':') {
+        true => Account::try_from(value.to_string()),
+        false => Account::user(value),
+    }
+}
+
+/// Ensure that an account has the expected type
+fn expect_type(account: &Account, account_type: AccountType) -> Result<(), Error> {
+    if account.account_type != account_type {
+        return Err(Error::ValidationError(format!(
+            "Expected an account of type {:?}, got {}",
+            account_type, account
+        )));
+    }
+    Ok(())
+}
+
+/// Build a transaction from the subcommand arguments
+fn build(
+    repo: &Repo,
+    from: Account,
+    to: Account,
+    args: &TransactionArgs,
+) -> Result<Transaction, Error> {
+    let amount = match &args.currency {
+        Some(code) => repo.parse_amount_in(&args.amount, code)?,
+        None => repo.parse_amount(&args.amount)?,
+    };
+    let meta = match (&args.class, args.ean) {
+        (None, None) => None,
+        (class, ean) => Some(TransactionMeta {
+            class: class.clone(),
+            ean,
+            import_hash: None,
+        }),
+    };
+    Ok(Transaction {
+        from,
+        to,
+        amount: amount.minor_units(),
+        currency: args.currency.clone(),
+        description: args.description.clone(),
+        meta,
+        exchange: None,
+    })
+}
+
+/// Check a transaction, then commit it, or only print its commit message in
+/// a dry run
+fn submit(
+    repo: &mut Repo,
+    transaction: Transaction,
+    dry_run: bool,
+    options: TransactionOptions,
+) -> Result<(), Error> {
+    repo.check_transaction(&transaction, &options)?;
+    commit(repo, transaction, dry_run, options)
+}
+
+/// Commit a transaction, or only print its commit message in a dry run
+fn commit(
+    repo: &mut Repo,
+    transaction: Transaction,
+    dry_run: bool,
+    options: TransactionOptions,
+) -> Result<(), Error> {
+    if dry_run {
+        println!("{}", repo.transaction_message(&transaction)?);
+        return Ok(());
+    }
+    let summary = transaction.summary(repo.config());
+    repo.create_transaction_with_options(transaction, options)?;
+    println!("✅ {}", summary);
+    Ok(())
+}
+
+/// A user pays a point of sale
+pub fn pay(
+    repo: &mut Repo,
+    from: Account,
+    to: Account,
+    args: TransactionArgs,
+) -> Result<(), Error> {
+    expect_type(&from, AccountType::User)?;
+    expect_type(&to, AccountType::PointOfSale)?;
+    let transaction = build(repo, from, to, &args)?;
+    submit(repo, transaction, args.dry_run, args.options())
+}
+
+/// Money from a source is deposited into a user account
+pub fn deposit(
+    repo: &mut Repo,
+    from: Account,
+    to: Account,
+    args: TransactionArgs,
+) -> Result<(), Error> {
+    expect_type(&from, AccountType::Source)?;
+    expect_type(&to, AccountType::User)?;
+    let transaction = build(repo, from, to, &args)?;
+    submit(repo, transaction, args.dry_run, args.options())
+}
+
+/// A user transfers money to another user
+pub fn transfer(
+    repo: &mut Repo,
+    from: Account,
+    to: Account,
+    args: TransactionArgs,
+) -> Result<(), Error> {
+    expect_type(&from, AccountType::User)?;
+    expect_type(&to, AccountType::User)?;
+    if from == to {
+        return Err(Error::ValidationError(
+            "Cannot transfer money to the same account".into(),
+        ));
+    }
+    let transaction = build(repo, from, to, &args)?;
+    submit(repo, transaction, args.dry_run, args.options())
+}
+
+/// A user exchanges money with another user: `from` pays the amount to `to`
+/// and receives the amount converted into the currency `into` at `rate`
+pub fn exchange(
+    repo: &mut Repo,
+    from: Account,
+    to: Account,
+    into: &str,
+    rate: &str,
+    args: TransactionArgs,
+) -> Result<(), Error> {
+    expect_type(&from, AccountType::User)?;
+    expect_type(&to, AccountType::User)?;
+    if from == to {
+        return Err(Error::ValidationError(
+            "Cannot exchange money with the same account".into(),
+        ));
+    }
+    let mut transaction = build(repo, from, to, &args)?;
+    let config = repo.config();
+    let find_currency = |code: &str| {
+        config
+            .find_currency(Some(code))
+            .ok_or_else(|| Error::ValidationError(format!("Unknown currency: {}", code)))
+    };
+    let source = find_currency(args.currency.as_deref().unwrap_or(&config.currency.code))?;
+    let amount = Money::from_minor_units(transaction.amount);
+    transaction.exchange = Some(Exchange::at_rate(
+        amount,
+        source,
+        find_currency(into)?,
+        rate,
+    )?);
+    submit(repo, transaction, args.dry_run, args.options())
+}
+
+/// Create a user account with an empty deposit (same as the interactive
+/// `adduser` command) and add it to the account registry
+pub fn add_user(
+    repo: &mut Repo,
+    name: &str,
+    display_name: Option<String>,
+    contact: Option<String>,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let account = Account::user(name)?;
+    if repo.accounts().contains(&account) {
+        return Err(Error::ValidationError(format!(
+            "Account already exists: {}",
+            account
+        )));
+    }
+    let transaction = Transaction {
+        from: Account::source("cash")?,
+        to: account,
+        amount: 0,
+        currency: None,
+        description: Some(format!("Create user {}", name)),
+        meta: None,
+        exchange: None,
+    };
+    commit(repo, transaction, dry_run, TransactionOptions::default())?;
+    if dry_run {
+        return Ok(());
+    }
+    repo.set_account_info(AccountInfo {
+        account: Account::user(name)?,
+        display_name,
+        contact,
+        created: today(),
+        archived: false,
+    })?;
+    println!("✅ Registered account user:{}", name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use libgitcash::Repo;
+
+    use super::*;
+    use crate::test_utils::{TestRepo, DEPOSIT};
+
+    fn args(amount: &str) -> TransactionArgs {
+        TransactionArgs {
+            amount: amount.into(),
+            currency: None,
+            description: None,
+            class: None,
+            ean: None,
+            allow_overdraft: false,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn test_exchange() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        let data = "from = \"source:cash\"\nto = \"user:bank\"\namount = 0";
+        test_repo.commit_transaction("Create user bank", data);
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        let danilo = Account::user("danilo").unwrap();
+        let bank = Account::user("bank").unwrap();
+
+        exchange(&mut repo, danilo.clone(), bank, "EUR", "0.95", args("10")).unwrap();
+        let recorded = repo.transactions()[2]
+            .transaction
+            .exchange
+            .as_ref()
+            .unwrap();
+        assert_eq!(recorded.currency, "EUR");
+        assert_eq!(recorded.amount, 950);
+        assert_eq!(recorded.rate, "0.95");
+        assert_eq!(repo.balances_in("EUR").unwrap()[&danilo], 950);
+
+        // Unknown currency, invalid rate and exchange with oneself
+        let bank = Account::user("bank").unwrap();
+        for (to, into, rate) in [
+            (bank.clone(), "USD", "1.1"),
+            (bank, "EUR", "abc"),
+            (danilo.clone(), "EUR", "0.95"),
+        ] {
+            let result = exchange(&mut repo, danilo.clone(), to, into, rate, args("1"));
+            assert!(matches!(result, Err(Error::ValidationError(_))));
+        }
+        assert_eq!(repo.transactions().len(), 3);
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    fs,
+    io::{self, Write},
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use git2::Oid;
+use libgitcash::{Repo, TransactionRecord};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::config::{Config, Sink};
+
+/// Timeout for connecting to a webhook and for each read and write
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Event emitted for every new or reverted transaction
+#[derive(Serialize)]
+struct Event<'a> {
+    /// Type of the event, "transaction" or "revert"
+    event: &'static str,
+    /// The new or reverted transaction
+    #[serde(flatten)]
+    record: &'a TransactionRecord,
+    /// The amount formatted in the currency of the transaction
+    formatted_amount: String,
+}
+
+impl Sink {
+    /// Send a JSON-serialized event to this sink
+    fn send(&self, event: &str) -> anyhow::Result<()> {
+        match self {
+            Sink::Script { command } => {
+                let mut child = Command::new(command)
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .context(format!("Could not run {:?}", command))?;
+                // Wait for the script even if it exits without reading the
+                // whole event
+                let written = child
+                    .stdin
+                    .take()
+                    .expect("stdin is piped")
+                    .write_all(event.as_bytes());
+                let status = child.wait()?;
+                if !status.success() {
+                    bail!("{:?} failed with {}", command, status);
+                }
+                written.context(format!("Could not pass the event to {:?}", command))?;
+            }
+            Sink::File { path } => {
+                let mut file = fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .context(format!("Could not open {:?}", path))?;
+                writeln!(file, "{}", event).context(format!("Could not write to {:?}", path))?;
+            }
+            Sink::Webhook { url } => {
+                ureq::AgentBuilder::new()
+                    .timeout_connect(WEBHOOK_TIMEOUT)
+                    .timeout_read(WEBHOOK_TIMEOUT)
+                    .timeout_write(WEBHOOK_TIMEOUT)
+                    .build()
+                    .post(url)
+                    .set("Content-Type", "application/json")
+                    .send_string(event)
+                    .context(format!("Could not POST to {}", url))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read the ID of the last processed commit from the state file
+fn read_state(config: &Config) -> anyhow::Result<Option<Oid>> {
+    let state_file = &config.watch.state_file;
+    let state = match fs::read_to_string(state_file) {
+        Ok(state) => state,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context(format!("Could not read state file {:?}", state_file)),
+    };
+    let commit = state
+        .trim()
+        .parse()
+        .context(format!("Invalid commit ID in state file {:?}", state_file))?;
+    Ok(Some(commit))
+}
+
+/// Emit events for all transactions and reverts since the last processed
+/// commit
+///
+/// Without a state file, or if the last processed commit is not part of the
+/// history anymore (e.g. after a force-push), only the current commit is
+/// recorded. The state is only updated after all events were delivered, so
+/// that failed deliveries are retried (which can lead to duplicate events in
+/// other sinks).
+fn process(repo: &mut Repo, config: &Config) -> anyhow::Result<()> {
+    if config.watch.sync {
+        repo.sync(&config.remote)?;
+    } else {
+        repo.reload()?;
+    }
+    let head = repo.head()?;
+    match read_state(config)? {
+        None => info!("Starting to watch at commit {}", head),
+        Some(last) if last == head => return Ok(()),
+        Some(last) if !repo.contains_commit(last)? => {
+            warn!(
+                "Last processed commit {} is not in the history anymore, resyncing at commit {}",
+                last, head
+            );
+        }
+        Some(last) => {
+            let transactions = repo.transactions_since(last)?;
+            let reverts = repo.reverts_since(last)?;
+            let events = transactions
+                .into_iter()
+                .map(|record| ("transaction", record))
+                .chain(reverts.into_iter().map(|record| ("revert", record)));
+            for (event, record) in events {
+                send_event(repo, config, event, record)?;
+                info!("Processed {} of transaction {}", event, record.id);
+            }
+        }
+    }
+    fs::write(&config.watch.state_file, head.to_string()).context(format!(
+        "Could not write state file {:?}",
+        config.watch.state_file
+    ))
+}
+
+/// Send an event about a transaction to all sinks
+fn send_event(
+    repo: &Repo,
+    config: &Config,
+    event: &'static str,
+    record: &TransactionRecord,
+) -> anyhow::Result<()> {
+    let repo_config = repo.config();
+    let currency = repo_config
+        .find_currency(record.transaction.currency.as_deref())
+        .unwrap_or(&repo_config.currency);
+    let event = serde_json::to_string(&Event {
+        event,
+        record,
+        formatted_amount: currency.format_amount(record.transaction.amount.into()),
+    })?;
+    for sink in &config.watch.sinks {
+        sink.send(&event)?;
+    }
+    Ok(())
+}
+
+/// Watch the repository for new transactions
+///
+/// Errors are logged and retried at the next check, unless `once` is set.
+pub fn watch(repo: &mut Repo, config: &Config, once: bool) -> anyhow::Result<()> {
+    if once {
+        return process(repo, config);
+    }
+    loop {
+        if let Err(e) = process(repo, config) {
+            warn!("{:#}", e);
+        }
+        thread::sleep(Duration::from_secs(config.watch.interval));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::test_utils::{TestRepo, DEPOSIT, PURCHASE};
+
+    /// Create a config that writes events to a file, return it together with
+    /// a function that reads all events
+    fn setup(test_repo: &TestRepo) -> (Config, impl Fn() -> Vec<Value>) {
+        let mut config = test_repo.config();
+        let path = test_repo.path().join("events");
+        config.watch.sinks = vec![Sink::File { path: path.clone() }];
+        let events = move || {
+            fs::read_to_string(&path)
+                .unwrap_or_default()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        };
+        (config, events)
+    }
+
+    #[test]
+    fn test_transactions_and_reverts() {
+        let test_repo = TestRepo::new();
+        let (config, events) = setup(&test_repo);
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+
+        // The first check only records the current commit
+        process(&mut repo, &config).unwrap();
+        assert!(events().is_empty());
+
+        let purchase = test_repo.commit_transaction("Purchase", PURCHASE);
+        process(&mut repo, &config).unwrap();
+        test_repo.commit_revert("Purchase", purchase);
+        process(&mut repo, &config).unwrap();
+        let events = events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["event"], "transaction");
+        assert_eq!(events[0]["id"], purchase.to_string());
+        assert_eq!(events[0]["formatted_amount"], "2.50 CHF");
+        assert_eq!(events[1]["event"], "revert");
+        assert_eq!(events[1]["id"], purchase.to_string());
+        assert!(events[1]["reverted_by"].is_string());
+    }
+
+    #[test]
+    fn test_rewritten_history() {
+        let test_repo = TestRepo::new();
+        let (config, events) = setup(&test_repo);
+        let head = test_repo.commit_transaction("Deposit", DEPOSIT);
+        let unknown = "0123456789012345678901234567890123456789";
+        fs::write(&config.watch.state_file, unknown).unwrap();
+
+        // The watcher resyncs at the current commit
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        process(&mut repo, &config).unwrap();
+        assert!(events().is_empty());
+        assert_eq!(read_state(&config).unwrap(), Some(head));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_script_exits_early() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // The script exits without reading an event larger than the pipe
+        // buffer, its exit status is still reported
+        let test_repo = TestRepo::new();
+        let command = test_repo.path().join("script.sh");
+        fs::write(&command, "#!/bin/sh\nexit 3\n").unwrap();
+        fs::set_permissions(&command, fs::Permissions::from_mode(0o755)).unwrap();
+        let sink = Sink::Script { command };
+        let err = sink.send(&"x".repeat(1_000_000)).unwrap_err();
+        assert!(err.to_string().contains("failed with"), "{}", err);
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use crate::{
+    config::Currency, error::Error, record::TransactionRecord, transaction::Account, Repo,
+};
+
+/// A transaction from the perspective of a single account
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryEntry<'a> {
+    /// The transaction
+    pub record: &'a TransactionRecord,
+    /// The other account involved in the transaction
+    pub counterparty: &'a Account,
+    /// The currency of the amount and balance
+    pub currency: &'a Currency,
+    /// The amount credited to the account (negative if it was debited)
+    pub amount: i64,
+    /// The balance of the account in this currency after this transaction
+    pub balance: i64,
+}
+
+impl Repo {
+    /// Return all transactions touching the specified account from oldest to
+    /// newest, together with the running balance of the account
+    ///
+    /// Exchanges result in one entry per currency. Reverted transactions and
+    /// transactions in unknown currencies are not included.
+    pub fn history(&self, account: &Account) -> Result<Vec<HistoryEntry<'_>>, Error> {
+        let mut balances: HashMap<&str, i64> = HashMap::new();
+        let mut entries = Vec::new();
+        for record in self.transactions.iter().filter(|r| !r.is_reverted()) {
+            for posting in record.transaction.postings() {
+                let currency = match self.config.find_currency(posting.currency) {
+                    Some(currency) => currency,
+                    None => continue,
+                };
+                let (counterparty, amount) = match (posting.from, posting.to) {
+                    (from, to) if from == account && to == account => (to, 0),
+                    (from, to) if from == account => (to, -posting.amount),
+                    (from, to) if to == account => (from, posting.amount),
+                    _ => continue,
+                };
+                let balance = balances.entry(&currency.code).or_default();
+                *balance = balance
+                    .checked_add(amount)
+                    .ok_or_else(|| Error::BalanceOverflowError(record.id, account.clone()))?;
+                entries.push(HistoryEntry {
+                    record,
+                    counterparty,
+                    currency,
+                    amount,
+                    balance: *balance,
+                });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_utils::TestRepo, Account, Repo};
+
+    #[test]
+    fn test_history() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction(
+            "Deposit",
+            "from = \"source:cash\"\nto = \"user:a\"\namount = 2000",
+        );
+        test_repo.commit_transaction(
+            "Other deposit",
+            "from = \"source:cash\"\nto = \"user:b\"\namount = 1000",
+        );
+        test_repo.commit_transaction(
+            "Purchase",
+            "from = \"user:a\"\nto = \"pos:fridge\"\namount = 250",
+        );
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        let account = Account::user("a").unwrap();
+        let history = repo.history(&account).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].counterparty, &Account::source("cash").unwrap());
+        assert_eq!((history[0].amount, history[0].balance), (2000, 2000));
+        assert_eq!(
+            history[1].counterparty,
+            &Account::point_of_sale("fridge").unwrap()
+        );
+        assert_eq!((history[1].amount, history[1].balance), (-250, 1750));
+    }
+}
@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+use crate::{error::Error, record::TransactionRecord, transaction::Account};
+
+/// Balances of all accounts, by currency code
+pub type CurrencyBalances = HashMap<String, HashMap<Account, i64>>;
+
+/// Calculate the balances of all accounts in all currencies
+///
+/// Transactions without a currency are booked in the primary currency.
+/// Reverted transactions are not taken into account. Balances are
+/// accumulated as `i64`, if they still overflow, an error pointing at the
+/// offending transaction is returned.
+pub fn compute_balances(
+    transactions: &[TransactionRecord],
+    primary_currency: &str,
+) -> Result<CurrencyBalances, Error> {
+    let mut balances: CurrencyBalances = HashMap::new();
+    for record in transactions.iter().filter(|r| !r.is_reverted()) {
+        for posting in record.transaction.postings() {
+            let accounts = balances
+                .entry(posting.currency.unwrap_or(primary_currency).to_string())
+                .or_default();
+            let source = accounts.entry(posting.from.clone()).or_default();
+            *source = source
+                .checked_sub(posting.amount)
+                .ok_or_else(|| Error::BalanceOverflowError(record.id, posting.from.clone()))?;
+            let destination = accounts.entry(posting.to.clone()).or_default();
+            *destination = destination
+                .checked_add(posting.amount)
+                .ok_or_else(|| Error::BalanceOverflowError(record.id, posting.to.clone()))?;
+        }
+    }
+    Ok(balances)
+}
@@ -0,0 +1,301 @@
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{
+    balance::{compute_balances, CurrencyBalances},
+    error::Error,
+    history::{History, LoadMode},
+    record::serde_oid,
+};
+
+/// Format version of the cache file, must be incremented whenever the
+/// serialized data changes
+const CACHE_VERSION: u32 = 3;
+
+/// File name of the cache (stored inside the `.git` directory)
+const CACHE_FILE_NAME: &str = "gitcash-cache.json";
+
+/// Parsed transactions and balances up to a certain commit
+#[derive(Debug, Deserialize)]
+struct Cache {
+    version: u32,
+    /// The last processed commit
+    #[serde(with = "serde_oid")]
+    head: Oid,
+    /// The primary currency the balances were computed with
+    primary_currency: String,
+    #[serde(flatten)]
+    history: History,
+    balances: CurrencyBalances,
+}
+
+/// Borrowed variant of [`Cache`] used for writing
+#[derive(Serialize)]
+struct CacheRef<'a> {
+    version: u32,
+    #[serde(with = "serde_oid")]
+    head: Oid,
+    primary_currency: &'a str,
+    #[serde(flatten)]
+    history: &'a History,
+    balances: &'a CurrencyBalances,
+}
+
+fn cache_path(repository: &Repository) -> PathBuf {
+    repository.path().join(CACHE_FILE_NAME)
+}
+
+impl Cache {
+    /// Read the cache, return `None` if there is no cache
+    fn read(repository: &Repository) -> Result<Option<Self>, Error> {
+        let cache_string = match fs::read_to_string(cache_path(repository)) {
+            Ok(cache_string) => cache_string,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::CacheError(format!("Could not read cache: {}", e))),
+        };
+        let cache: Cache = serde_json::from_str(&cache_string)
+            .map_err(|e| Error::CacheError(format!("Could not parse cache: {}", e)))?;
+        if cache.version != CACHE_VERSION {
+            return Err(Error::CacheError(format!(
+                "Unsupported cache version {}",
+                cache.version
+            )));
+        }
+        Ok(Some(cache))
+    }
+
+    /// Write the history and balances up to `head` to the cache
+    fn write(
+        repository: &Repository,
+        head: Oid,
+        history: &History,
+        primary_currency: &str,
+        balances: &CurrencyBalances,
+    ) -> Result<(), Error> {
+        let cache = CacheRef {
+            version: CACHE_VERSION,
+            head,
+            primary_currency,
+            history,
+            balances,
+        };
+        let cache_string = serde_json::to_string(&cache)
+            .map_err(|e| Error::CacheError(format!("Could not serialize cache: {}", e)))?;
+        fs::write(cache_path(repository), cache_string)
+            .map_err(|e| Error::CacheError(format!("Could not write cache: {}", e)))
+    }
+
+    /// Return whether the cache can be used as basis for loading `head`
+    ///
+    /// This is not the case if the cached commit is not an ancestor of `head`
+    /// anymore (i.e. the history was rewritten), if the primary currency
+    /// changed, or if the cache contains skipped commits but strict mode is
+    /// requested.
+    fn is_usable(
+        &self,
+        repository: &Repository,
+        head: Oid,
+        mode: LoadMode,
+        primary_currency: &str,
+    ) -> bool {
+        if mode == LoadMode::Strict && !self.history.diagnostics.is_empty() {
+            return false;
+        }
+        if self.primary_currency != primary_currency {
+            debug!("Primary currency changed from {}", self.primary_currency);
+            return false;
+        }
+        let reachable = self.head == head
+            || repository
+                .graph_descendant_of(head, self.head)
+                .unwrap_or(false);
+        if !reachable {
+            debug!("Cached commit {} is not an ancestor of HEAD", self.head);
+        }
+        reachable
+    }
+}
+
+/// Load the history and balances up to `head`, reusing and updating the
+/// cache
+///
+/// If the cache is up to date, the cached balances are returned as they are.
+/// Otherwise, only commits that are not in the cache yet are parsed. If the
+/// cache is unusable, it is rebuilt from scratch. The balances are `None` if
+/// they cannot be computed (e.g. because they overflow).
+pub(crate) fn load_cached_history(
+    repository: &Repository,
+    head: Oid,
+    mode: LoadMode,
+    primary_currency: &str,
+) -> Result<(History, Option<CurrencyBalances>), Error> {
+    let cache = Cache::read(repository).unwrap_or_else(|e| {
+        warn!("Ignoring cache: {}", e);
+        None
+    });
+    let history = match cache {
+        Some(mut cache) if cache.is_usable(repository, head, mode, primary_currency) => {
+            if cache.head == head {
+                return Ok((cache.history, Some(cache.balances)));
+            }
+            debug!("Loading new commits since cached commit {}", cache.head);
+            cache
+                .history
+                .extend(repository, head, &[cache.head], mode)?;
+            cache.history
+        }
+        _ => {
+            debug!("Rebuilding cache");
+            History::load(repository, head, mode)?
+        }
+    };
+    let balances = compute_balances(&history.transactions, primary_currency).ok();
+    if let Some(balances) = &balances {
+        if let Err(e) = Cache::write(repository, head, &history, primary_currency, balances) {
+            warn!("{}", e);
+        }
+    }
+    Ok((history, balances))
+}
+
+/// Compare the cache against a full rebuild, return the cached commit ID
+pub(crate) fn verify_cache(
+    repository: &Repository,
+    mode: LoadMode,
+    primary_currency: &str,
+) -> Result<Oid, Error> {
+    let cache =
+        Cache::read(repository)?.ok_or_else(|| Error::CacheError("There is no cache".into()))?;
+    let rebuilt = History::load(repository, cache.head, mode)?;
+
+    // The order of transactions from different branches may differ, so
+    // compare by commit ID
+    if rebuilt.transactions.len() != cache.history.transactions.len() {
+        return Err(Error::CacheError(format!(
+            "Cache contains {} transactions, but {} were expected",
+            cache.history.transactions.len(),
+            rebuilt.transactions.len()
+        )));
+    }
+    let cached = cache
+        .history
+        .transactions
+        .iter()
+        .map(|record| (record.id, record))
+        .collect::<HashMap<_, _>>();
+    for record in &rebuilt.transactions {
+        if cached.get(&record.id) != Some(&record) {
+            return Err(Error::CacheError(format!(
+                "Cached transaction {} does not match the repository",
+                record.id
+            )));
+        }
+    }
+    let mut cached_restocks = cache.history.restocks.iter().collect::<Vec<_>>();
+    let mut rebuilt_restocks = rebuilt.restocks.iter().collect::<Vec<_>>();
+    cached_restocks.sort_by_key(|record| record.id);
+    rebuilt_restocks.sort_by_key(|record| record.id);
+    if cached_restocks != rebuilt_restocks {
+        return Err(Error::CacheError(
+            "Cached restocks do not match the repository".into(),
+        ));
+    }
+    let mut cached_diagnostics = cache.history.diagnostics.clone();
+    let mut rebuilt_diagnostics = rebuilt.diagnostics.clone();
+    cached_diagnostics.sort_by_key(|diagnostic| diagnostic.commit);
+    rebuilt_diagnostics.sort_by_key(|diagnostic| diagnostic.commit);
+    if cached_diagnostics != rebuilt_diagnostics {
+        return Err(Error::CacheError(
+            "Cached diagnostics do not match the repository".into(),
+        ));
+    }
+
+    if compute_balances(&rebuilt.transactions, primary_currency)? != cache.balances {
+        return Err(Error::CacheError(
+            "Cached balances do not match the repository".into(),
+        ));
+    }
+    Ok(cache.head)
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Repository;
+
+    use super::*;
+    use crate::{test_utils::TestRepo, transaction::Account};
+
+    fn load(repository: &Repository, head: Oid) -> usize {
+        load_cached_history(repository, head, LoadMode::Strict, "CHF")
+            .unwrap()
+            .0
+            .transactions
+            .len()
+    }
+
+    const PURCHASE: &str = "from = \"user:danilo\"\nto = \"pos:fridge\"\namount = 250";
+
+    #[test]
+    fn test_incremental_and_rewritten() {
+        let test_repo = TestRepo::new();
+        let first = test_repo.commit_transaction("Purchase", PURCHASE);
+        let repository = Repository::open(test_repo.path()).unwrap();
+
+        // Build cache
+        assert_eq!(load(&repository, first), 1);
+        assert_eq!(
+            verify_cache(&repository, LoadMode::Strict, "CHF").unwrap(),
+            first
+        );
+
+        // New commits are appended
+        let second = test_repo.commit_transaction("Purchase", PURCHASE);
+        assert_eq!(load(&repository, second), 2);
+        assert_eq!(
+            verify_cache(&repository, LoadMode::Strict, "CHF").unwrap(),
+            second
+        );
+
+        // Rewritten history drops the cache
+        let initial = test_repo
+            .repo
+            .find_commit(first)
+            .unwrap()
+            .parent_id(0)
+            .unwrap();
+        assert_eq!(load(&repository, initial), 0);
+        assert_eq!(
+            verify_cache(&repository, LoadMode::Strict, "CHF").unwrap(),
+            initial
+        );
+    }
+
+    #[test]
+    fn test_cached_balances() {
+        let test_repo = TestRepo::new();
+        let head = test_repo.commit_transaction("Purchase", PURCHASE);
+        let repository = Repository::open(test_repo.path()).unwrap();
+        let danilo = Account::user("danilo").unwrap();
+        let (history, balances) =
+            load_cached_history(&repository, head, LoadMode::Strict, "CHF").unwrap();
+        assert_eq!(balances.unwrap()["CHF"][&danilo], -250);
+
+        // An up to date cache is used as it is
+        let mut balances = CurrencyBalances::new();
+        balances
+            .entry("CHF".into())
+            .or_default()
+            .insert(danilo.clone(), -1);
+        Cache::write(&repository, head, &history, "CHF", &balances).unwrap();
+        let (_, cached) = load_cached_history(&repository, head, LoadMode::Strict, "CHF").unwrap();
+        assert_eq!(cached, Some(balances));
+
+        // A different primary currency drops the cache
+        let (_, balances) =
+            load_cached_history(&repository, head, LoadMode::Strict, "EUR").unwrap();
+        assert_eq!(balances.unwrap()["EUR"][&danilo], -250);
+    }
+}
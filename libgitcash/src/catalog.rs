@@ -0,0 +1,187 @@
+use std::{io, path::Path, str::FromStr};
+
+use git2::{Commit, Repository};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::read_committed_file, error::Error, Repo};
+
+/// File name of the product catalog
+pub(crate) const CATALOG_FILE_NAME: &str = "products.toml";
+
+/// A product that can be bought by scanning its barcode
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub struct Product {
+    /// The EAN code
+    pub ean: u64,
+    /// Human-readable name, used as transaction description
+    pub name: String,
+    /// The price (as integer, see currency divisor)
+    pub price: i32,
+    /// The product class, e.g. "softdrink"
+    pub class: Option<String>,
+    /// Stock level at or below which the product is running low
+    pub min_stock: Option<u32>,
+}
+
+/// The product catalog (`products.toml`) of a repository
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Catalog {
+    #[serde(default, rename = "product")]
+    pub products: Vec<Product>,
+}
+
+impl Catalog {
+    /// Load the catalog in the specified repo path (empty if there is none)
+    pub fn load(repo_path: &Path) -> Result<Self, Error> {
+        match std::fs::read_to_string(repo_path.join(CATALOG_FILE_NAME)) {
+            Ok(catalog_string) => Self::from_str(&catalog_string),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Error::RepoError(format!(
+                "Could not read {}: {}",
+                CATALOG_FILE_NAME, e
+            ))),
+        }
+    }
+
+    /// Load the catalog from a commit (empty if there is none)
+    pub fn from_commit(repository: &Repository, commit: &Commit) -> Result<Self, Error> {
+        match read_committed_file(repository, commit, CATALOG_FILE_NAME)? {
+            Some(catalog_string) => Self::from_str(&catalog_string),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Look up a product by EAN
+    pub fn lookup(&self, ean: u64) -> Option<&Product> {
+        self.products.iter().find(|product| product.ean == ean)
+    }
+
+    /// Add a product, after validating its EAN and price
+    pub fn add(&mut self, product: Product) -> Result<(), Error> {
+        validate_ean(product.ean)?;
+        if product.price < 0 {
+            return Err(Error::ValidationError(format!(
+                "Price of {} may not be negative",
+                product.name
+            )));
+        }
+        if let Some(existing) = self.lookup(product.ean) {
+            return Err(Error::ValidationError(format!(
+                "EAN {} is already used for {}",
+                product.ean, existing.name
+            )));
+        }
+        self.products.push(product);
+        Ok(())
+    }
+}
+
+impl FromStr for Catalog {
+    type Err = Error;
+
+    fn from_str(catalog_string: &str) -> Result<Self, Error> {
+        toml::from_str(catalog_string)
+            .map_err(|e| Error::RepoError(format!("Could not parse {}: {}", CATALOG_FILE_NAME, e)))
+    }
+}
+
+/// Validate the check digit of an EAN-8, UPC-A, EAN-13 or GTIN-14 code
+pub fn validate_ean(ean: u64) -> Result<(), Error> {
+    let digits = ean.to_string();
+    if ean == 0 || digits.len() > 14 {
+        return Err(Error::ValidationError(format!("Invalid EAN: {}", ean)));
+    }
+
+    // Starting from the right (without check digit), digits are weighted
+    // alternately with 3 and 1
+    let check_digit = ean % 10;
+    let sum: u64 = digits
+        .bytes()
+        .rev()
+        .skip(1)
+        .zip([3, 1].into_iter().cycle())
+        .map(|(digit, weight)| u64::from(digit - b'0') * weight)
+        .sum();
+    if (10 - sum % 10) % 10 != check_digit {
+        return Err(Error::ValidationError(format!(
+            "Invalid EAN {}: Wrong check digit",
+            ean
+        )));
+    }
+    Ok(())
+}
+
+impl Repo {
+    /// Return the product catalog
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    /// Add a product to the catalog and commit the updated `products.toml`
+    pub fn add_product(&mut self, product: Product) -> Result<(), Error> {
+        let workdir = self
+            .repository
+            .workdir()
+            .ok_or_else(|| Error::RepoError("Cannot add products in a bare repository".into()))?
+            .to_path_buf();
+        let message = format!("Add product {} ({})", product.name, product.ean);
+        self.catalog.add(product)?;
+
+        // Write and commit catalog
+        let catalog_string = toml::to_string(&self.catalog)
+            .map_err(|e| Error::RepoError(format!("Could not serialize catalog: {}", e)))?;
+        std::fs::write(workdir.join(CATALOG_FILE_NAME), catalog_string).map_err(|e| {
+            Error::RepoError(format!("Could not write {}: {}", CATALOG_FILE_NAME, e))
+        })?;
+        let mut index = self.repository.index()?;
+        index.add_path(Path::new(CATALOG_FILE_NAME))?;
+        index.write()?;
+        self.commit(&message)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestRepo;
+
+    fn product(ean: u64) -> Product {
+        Product {
+            ean,
+            name: "Vivi Kola 33cl".into(),
+            price: 250,
+            class: Some("softdrink".into()),
+            min_stock: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_ean() {
+        assert!(validate_ean(7610867035003).is_ok());
+        assert!(validate_ean(96385074).is_ok());
+        assert!(validate_ean(36000291452).is_ok()); // UPC-A with leading zero
+        assert!(validate_ean(7610867035004).is_err());
+        assert!(validate_ean(0).is_err());
+    }
+
+    #[test]
+    fn test_add_product() {
+        let test_repo = TestRepo::new();
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        repo.add_product(product(7610867035003)).unwrap();
+        assert!(repo.add_product(product(7610867035003)).is_err());
+        assert!(repo.add_product(product(7610867035004)).is_err());
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        assert_eq!(
+            repo.catalog().lookup(7610867035003),
+            Some(&product(7610867035003))
+        );
+        let head = test_repo.repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(
+            head.message(),
+            Some("Add product Vivi Kola 33cl (7610867035003)")
+        );
+    }
+}
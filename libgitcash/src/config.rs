@@ -1,39 +1,304 @@
-use std::path::Path;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    str::FromStr,
+};
 
+use git2::{Commit, Repository};
 use serde::{Deserialize, Serialize};
 
-use crate::error::Error;
+use crate::{
+    error::Error,
+    transaction::{Account, AccountType},
+};
 
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct RepoConfig {
     pub name: String,
+    /// The primary currency, used by transactions without a currency
     pub currency: Currency,
+    /// Additional currencies
+    #[serde(default, rename = "other_currency")]
+    pub other_currencies: Vec<Currency>,
+    /// Minimum balances of accounts
+    #[serde(default)]
+    pub limits: Limits,
 }
 
+/// File name of the repo config
+const CONFIG_FILE_NAME: &str = "gitcash.toml";
+
 impl RepoConfig {
     /// Load repo config in the specified repo path
     pub fn load(repo_path: &Path) -> Result<Self, Error> {
-        let config_string = std::fs::read_to_string(repo_path.join("gitcash.toml"))
+        let config_string = std::fs::read_to_string(repo_path.join(CONFIG_FILE_NAME))
             .map_err(|e| Error::RepoError(format!("Could not read gitcash.toml: {}", e)))?;
         Self::from_str(&config_string)
     }
 
-    pub fn from_str(config_string: &str) -> Result<Self, Error> {
-        let config: RepoConfig = toml::from_str(&config_string)
+    /// Load repo config from a commit (works in bare repositories as well)
+    pub fn from_commit(repository: &Repository, commit: &Commit) -> Result<Self, Error> {
+        let config_string =
+            read_committed_file(repository, commit, CONFIG_FILE_NAME)?.ok_or_else(|| {
+                Error::RepoError(format!("Commit {} contains no gitcash.toml", commit.id()))
+            })?;
+        Self::from_str(&config_string)
+    }
+
+    /// Return all currencies, starting with the primary one
+    pub fn currencies(&self) -> impl Iterator<Item = &Currency> {
+        std::iter::once(&self.currency).chain(&self.other_currencies)
+    }
+
+    /// Return the currency with the specified code, or the primary currency
+    /// if no code is specified
+    pub fn find_currency(&self, code: Option<&str>) -> Option<&Currency> {
+        match code {
+            Some(code) => self.currencies().find(|currency| currency.code == code),
+            None => Some(&self.currency),
+        }
+    }
+}
+
+/// Read a file in the root directory of a commit, return `None` if it does
+/// not exist
+pub(crate) fn read_committed_file(
+    repository: &Repository,
+    commit: &Commit,
+    file_name: &str,
+) -> Result<Option<String>, Error> {
+    let tree = commit.tree()?;
+    let entry = match tree.get_name(file_name) {
+        Some(entry) => entry.to_object(repository)?,
+        None => return Ok(None),
+    };
+    let blob = entry.as_blob().ok_or_else(|| {
+        Error::RepoError(format!(
+            "{} in commit {} is not a file",
+            file_name,
+            commit.id()
+        ))
+    })?;
+    let content = std::str::from_utf8(blob.content())
+        .map_err(|e| Error::RepoError(format!("Could not read {}: {}", file_name, e)))?;
+    Ok(Some(content.to_string()))
+}
+
+impl FromStr for RepoConfig {
+    type Err = Error;
+
+    fn from_str(config_string: &str) -> Result<Self, Error> {
+        let config: RepoConfig = toml::from_str(config_string)
             .map_err(|e| Error::RepoError(format!("Could not parse gitcash.toml: {}", e)))?;
+        let mut codes = HashSet::new();
+        for currency in config.currencies() {
+            currency.validate()?;
+            if !codes.insert(&currency.code) {
+                return Err(Error::RepoError(format!(
+                    "Currency {} is configured more than once",
+                    currency.code
+                )));
+            }
+        }
         Ok(config)
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+/// Minimum balances in the primary currency (the `[limits]` section)
+///
+/// All amounts are integers (see currency divisor). Accounts without a limit
+/// may go arbitrarily negative.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Limits {
+    /// Minimum balance of user accounts, e.g. `-2000`
+    pub user: Option<i64>,
+    /// Minimum balance of point of sale accounts
+    pub pos: Option<i64>,
+    /// Minimum balance of source accounts
+    pub source: Option<i64>,
+    /// Per-account overrides, e.g. `"user:danilo" = -5000`
+    #[serde(default)]
+    pub accounts: HashMap<Account, i64>,
+}
+
+impl Limits {
+    /// Return the minimum balance of an account, if it is limited
+    pub fn min_balance(&self, account: &Account) -> Option<i64> {
+        self.accounts
+            .get(account)
+            .copied()
+            .or(match account.account_type {
+                AccountType::User => self.user,
+                AccountType::PointOfSale => self.pos,
+                AccountType::Source => self.source,
+            })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct Currency {
     pub code: String,
     pub divisor: usize,
+    /// How amounts are displayed
+    #[serde(default)]
+    pub format: CurrencyFormat,
+}
+
+/// Display format of amounts (the `[currency.format]` section)
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct CurrencyFormat {
+    /// Symbol shown instead of the currency code, e.g. "Fr." or "€"
+    pub symbol: Option<String>,
+    /// Whether the code or symbol is placed before or after the number
+    pub position: SymbolPosition,
+    /// Whether there is a space between the number and the code or symbol
+    pub spacing: bool,
+    /// Separator between groups of thousands, e.g. "'" (none by default)
+    pub thousands_separator: String,
+    /// Separator between the integer and the fractional part
+    pub decimal_separator: String,
+    /// How negative amounts are displayed
+    pub negative: NegativeStyle,
+}
+
+impl Default for CurrencyFormat {
+    fn default() -> Self {
+        Self {
+            symbol: None,
+            position: SymbolPosition::After,
+            spacing: true,
+            thousands_separator: String::new(),
+            decimal_separator: ".".into(),
+            negative: NegativeStyle::Minus,
+        }
+    }
+}
+
+/// Placement of the currency code or symbol
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolPosition {
+    /// E.g. `CHF 2.50`
+    Before,
+    /// E.g. `2.50 CHF`
+    After,
+}
+
+/// Display style of negative amounts
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NegativeStyle {
+    /// E.g. `-2.50 CHF`
+    Minus,
+    /// E.g. `(2.50 CHF)`
+    Parentheses,
+}
+
+impl Currency {
+    /// Ensure that the divisor is a power of ten and that the separators are
+    /// unambiguous
+    fn validate(&self) -> Result<(), Error> {
+        if self.divisor == 0 || 10usize.pow(self.decimals()) != self.divisor {
+            return Err(Error::RepoError(format!(
+                "Invalid divisor for currency {}, must be a power of ten: {}",
+                self.code, self.divisor
+            )));
+        }
+        let format = &self.format;
+        if format.decimal_separator.is_empty()
+            || format.decimal_separator == format.thousands_separator
+            || [&format.decimal_separator, &format.thousands_separator]
+                .iter()
+                .any(|separator| separator.contains(|c: char| c.is_ascii_digit() || c == '-'))
+        {
+            return Err(Error::RepoError(format!(
+                "Invalid separators for currency {}",
+                self.code
+            )));
+        }
+        Ok(())
+    }
+
+    /// Return the number of decimal places (e.g. 2 for a divisor of 100)
+    pub fn decimals(&self) -> u32 {
+        self.divisor.checked_ilog10().unwrap_or(0)
+    }
+
+    /// Return the code or, if configured, the symbol of the currency
+    pub fn unit(&self) -> &str {
+        self.format.symbol.as_deref().unwrap_or(&self.code)
+    }
+
+    /// Format an integer amount according to the configured format, e.g.
+    /// `2.50 CHF` for the amount 250 by default
+    pub fn format_amount(&self, amount: i64) -> String {
+        let format = &self.format;
+        let number = self.format_number(
+            amount.unsigned_abs(),
+            &format.thousands_separator,
+            &format.decimal_separator,
+        );
+        let number = match (amount < 0, format.negative) {
+            (true, NegativeStyle::Minus) => format!("-{}", number),
+            _ => number,
+        };
+        let space = if format.spacing { " " } else { "" };
+        let formatted = match format.position {
+            SymbolPosition::Before => format!("{}{}{}", self.unit(), space, number),
+            SymbolPosition::After => format!("{}{}{}", number, space, self.unit()),
+        };
+        match (amount < 0, format.negative) {
+            (true, NegativeStyle::Parentheses) => format!("({})", formatted),
+            _ => formatted,
+        }
+    }
+
+    /// Format an integer amount as plain decimal number without currency,
+    /// e.g. `2.50` for the amount 250 (ignoring the configured format)
+    pub fn format_decimal(&self, amount: i64) -> String {
+        let sign = if amount < 0 { "-" } else { "" };
+        format!(
+            "{}{}",
+            sign,
+            self.format_number(amount.unsigned_abs(), "", ".")
+        )
+    }
+
+    /// Format an unsigned integer amount with the specified separators
+    fn format_number(
+        &self,
+        amount: u64,
+        thousands_separator: &str,
+        decimal_separator: &str,
+    ) -> String {
+        let divisor = self.divisor as u64;
+        let major = (amount / divisor).to_string();
+        let mut integer = String::new();
+        for (i, digit) in major.chars().enumerate() {
+            if i > 0 && (major.len() - i) % 3 == 0 {
+                integer.push_str(thousands_separator);
+            }
+            integer.push(digit);
+        }
+        match self.decimals() {
+            0 => integer,
+            decimals => format!(
+                "{}{}{:0width$}",
+                integer,
+                decimal_separator,
+                amount % divisor,
+                width = decimals as usize
+            ),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Currency, RepoConfig};
+    use std::str::FromStr;
+
+    use super::*;
 
     #[test]
     fn test_from_str() {
@@ -48,10 +313,107 @@ mod tests {
                 name: "foo".to_owned(),
                 currency: Currency {
                     code: "CHF".to_owned(),
-                    divisor: 100
-                }
+                    divisor: 100,
+                    format: CurrencyFormat::default(),
+                },
+                other_currencies: vec![],
+                limits: Limits::default(),
             },
             repo_config
         );
     }
+
+    #[test]
+    fn test_limits() {
+        let repo_config_str = "name = \"foo\"\n[currency]\ncode = \"CHF\"\ndivisor = 100\n\
+                               [limits]\nuser = -2000\n\
+                               [limits.accounts]\n\"user:danilo\" = -5000";
+        let limits = RepoConfig::from_str(repo_config_str).unwrap().limits;
+        let min_balance =
+            |account: &str| limits.min_balance(&Account::try_from(account.to_string()).unwrap());
+        assert_eq!(min_balance("user:danilo"), Some(-5000));
+        assert_eq!(min_balance("user:rnestler"), Some(-2000));
+        assert_eq!(min_balance("source:cash"), None);
+    }
+
+    #[test]
+    fn test_other_currencies() {
+        let repo_config_str = "name = \"foo\"\n[currency]\ncode = \"CHF\"\ndivisor = 100\n\
+                               [[other_currency]]\ncode = \"EUR\"\ndivisor = 100";
+        let repo_config = RepoConfig::from_str(repo_config_str).unwrap();
+        assert_eq!(repo_config.find_currency(None).unwrap().code, "CHF");
+        assert_eq!(repo_config.find_currency(Some("EUR")).unwrap().code, "EUR");
+        assert!(repo_config.find_currency(Some("USD")).is_none());
+        let duplicate = repo_config_str.replace("EUR", "CHF");
+        assert!(RepoConfig::from_str(&duplicate).is_err());
+    }
+
+    #[test]
+    fn test_invalid_divisor() {
+        let repo_config_str = "name = \"foo\"\n[currency]\ncode = \"CHF\"\ndivisor = 20";
+        assert!(RepoConfig::from_str(repo_config_str).is_err());
+    }
+
+    #[test]
+    fn test_format_amount() {
+        let currency = Currency {
+            code: "CHF".to_owned(),
+            divisor: 100,
+            format: CurrencyFormat::default(),
+        };
+        assert_eq!(currency.format_amount(250), "2.50 CHF");
+        assert_eq!(currency.format_amount(-5), "-0.05 CHF");
+        let currency = Currency {
+            code: "XBT".to_owned(),
+            divisor: 1000,
+            format: CurrencyFormat::default(),
+        };
+        assert_eq!(currency.format_amount(1234567), "1234.567 XBT");
+        assert_eq!(currency.format_decimal(-1234567), "-1234.567");
+        let currency = Currency {
+            code: "JPY".to_owned(),
+            divisor: 1,
+            format: CurrencyFormat::default(),
+        };
+        assert_eq!(currency.format_amount(250), "250 JPY");
+    }
+
+    #[test]
+    fn test_custom_format() {
+        let repo_config_str = r#"name = "foo"
+               [currency]
+               code = "CHF"
+               divisor = 100
+               [currency.format]
+               symbol = "Fr."
+               position = "before"
+               thousands_separator = "'"
+               negative = "parentheses""#;
+        let currency = RepoConfig::from_str(repo_config_str).unwrap().currency;
+        assert_eq!(currency.format_amount(123456789), "Fr. 1'234'567.89");
+        assert_eq!(currency.format_amount(-99), "(Fr. 0.99)");
+        assert_eq!(currency.format_decimal(-123456789), "-1234567.89");
+
+        let currency = Currency {
+            code: "EUR".to_owned(),
+            divisor: 100,
+            format: CurrencyFormat {
+                symbol: Some("€".into()),
+                spacing: false,
+                thousands_separator: ".".into(),
+                decimal_separator: ",".into(),
+                ..Default::default()
+            },
+        };
+        assert_eq!(currency.format_amount(-100050), "-1.000,50€");
+        assert!(Currency {
+            format: CurrencyFormat {
+                thousands_separator: ".".into(),
+                ..Default::default()
+            },
+            ..currency
+        }
+        .validate()
+        .is_err());
+    }
 }
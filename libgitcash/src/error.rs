@@ -1,13 +1,38 @@
+use crate::transaction::Account;
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Repo error: {0}")]
     RepoError(String),
     #[error("Libgit error: {0}")]
     LibgitError(#[from] git2::Error),
+    #[error("Invalid commit {0}: {1}")]
+    CommitError(git2::Oid, Box<Error>),
     #[error("Could not parse transaction: {0}")]
     TransactionParseError(String),
     #[error("Could not serialize transaction: {0}")]
     TransactionSerializeError(String),
+    #[error("Could not parse amount: {0}")]
+    AmountParseError(String),
+    #[error("Balance of account {1} overflows in commit {0}")]
+    BalanceOverflowError(git2::Oid, Account),
+    #[error("Invalid revert: {0}")]
+    RevertError(String),
+    #[error("Cache error: {0}")]
+    CacheError(String),
+    #[error("Sync error: {0}")]
+    SyncError(String),
     #[error("Validation error: {0}")]
     ValidationError(String),
+    #[error(
+        "Balance of {account} would drop below its limit of {limit} (current balance: {balance})"
+    )]
+    CreditLimitError {
+        account: Account,
+        /// Current balance of the account
+        balance: i64,
+        /// Minimum balance of the account
+        limit: i64,
+    },
 }
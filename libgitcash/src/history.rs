@@ -0,0 +1,167 @@
+use std::fmt;
+
+use git2::{Oid, Repository, Sort};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{
+    error::Error,
+    inventory::{extract_restock, Restock, RestockRecord},
+    record::{serde_oid, TransactionRecord},
+    revert::find_reverted_transaction,
+    transaction::{
+        extract_title, extract_transaction, find_offending_line, Transaction, RESTOCK_PREFIX,
+        REVERT_PREFIX, TRANSACTION_PREFIX,
+    },
+};
+
+/// How to deal with commits that cannot be loaded
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoadMode {
+    /// Abort loading the repository
+    #[default]
+    Strict,
+    /// Skip the commit and record a [`Diagnostic`]
+    Lenient,
+}
+
+/// A commit that was skipped while loading the repository in lenient mode
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub struct Diagnostic {
+    /// ID of the skipped commit
+    #[serde(with = "serde_oid")]
+    pub commit: Oid,
+    /// Description of the error
+    pub error: String,
+    /// The offending line of the TOML transaction data, if known
+    pub line: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Skipped commit {}: {}", self.commit, self.error)?;
+        if let Some(line) = &self.line {
+            write!(f, " (in line `{}`)", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Transactions, restocks and diagnostics loaded from the commit history
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct History {
+    pub transactions: Vec<TransactionRecord>,
+    #[serde(default)]
+    pub restocks: Vec<RestockRecord>,
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl History {
+    /// Load all commits reachable from the specified commit
+    pub fn load(repository: &Repository, tip: Oid, mode: LoadMode) -> Result<Self, Error> {
+        let mut history = Self::default();
+        history.extend(repository, tip, &[], mode)?;
+        Ok(history)
+    }
+
+    /// Load all commits reachable from `tip` but not from any of the `known`
+    /// commits, and append them to the already loaded history
+    ///
+    /// Commits are traversed from oldest to newest. Reverts are applied to the
+    /// transactions they cancel. This works for both regular and bare
+    /// repositories.
+    pub fn extend(
+        &mut self,
+        repository: &Repository,
+        tip: Oid,
+        known: &[Oid],
+        mode: LoadMode,
+    ) -> Result<(), Error> {
+        let mut revwalk = repository.revwalk()?;
+        revwalk.push(tip)?;
+        for known in known {
+            revwalk.hide(*known)?;
+        }
+        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        for commit_oid in revwalk {
+            let commit = repository.find_commit(commit_oid?)?;
+            let message = match commit.message_raw() {
+                Some(msg) => msg,
+                None => continue,
+            };
+            if message.starts_with(TRANSACTION_PREFIX) {
+                debug!("Processing commit {}", commit.id());
+                match extract_transaction(message) {
+                    Ok(transaction) => self.transactions.push(TransactionRecord::new(
+                        &commit,
+                        extract_title(message, TRANSACTION_PREFIX).to_string(),
+                        transaction,
+                    )),
+                    Err(e) if mode == LoadMode::Lenient => self.diagnostics.push(Diagnostic {
+                        commit: commit.id(),
+                        error: e.to_string(),
+                        line: find_offending_line::<Transaction>(message),
+                    }),
+                    Err(e) => return Err(Error::CommitError(commit.id(), Box::new(e))),
+                }
+            } else if message.starts_with(REVERT_PREFIX) {
+                debug!("Processing revert commit {}", commit.id());
+                match find_reverted_transaction(&self.transactions, commit.id(), message) {
+                    Ok(index) => self.transactions[index].reverted_by = Some(commit.id()),
+                    Err(e) if mode == LoadMode::Lenient => self.diagnostics.push(Diagnostic {
+                        commit: commit.id(),
+                        error: e.to_string(),
+                        line: find_offending_line::<Transaction>(message),
+                    }),
+                    Err(e) => return Err(Error::CommitError(commit.id(), Box::new(e))),
+                }
+            } else if message.starts_with(RESTOCK_PREFIX) {
+                debug!("Processing restock commit {}", commit.id());
+                match extract_restock(message) {
+                    Ok(restock) => self.restocks.push(RestockRecord::new(&commit, restock)),
+                    Err(e) if mode == LoadMode::Lenient => self.diagnostics.push(Diagnostic {
+                        commit: commit.id(),
+                        error: e.to_string(),
+                        line: find_offending_line::<Restock>(message),
+                    }),
+                    Err(e) => return Err(Error::CommitError(commit.id(), Box::new(e))),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse all transactions reachable from the specified commit
+///
+/// Commits are traversed from oldest to newest. Reverts are applied to the
+/// transactions they cancel. This works for both regular and bare
+/// repositories. Any invalid commit results in an error.
+pub fn load_transactions(
+    repository: &Repository,
+    tip: Oid,
+) -> Result<Vec<TransactionRecord>, Error> {
+    Ok(History::load(repository, tip, LoadMode::Strict)?.transactions)
+}
+
+/// Parse the transactions reachable from `tip`, where only the commits that
+/// are not reachable from any of the `known` commits are new
+///
+/// Known commits were accepted before and are loaded leniently, so that a
+/// single invalid commit in the existing history doesn't block all further
+/// updates. Any invalid new commit results in an error. Returns all
+/// transactions, including the known ones.
+pub fn load_new_transactions(
+    repository: &Repository,
+    tip: Oid,
+    known: &[Oid],
+) -> Result<Vec<TransactionRecord>, Error> {
+    let mut history = History::default();
+    for (i, base) in known.iter().enumerate() {
+        history.extend(repository, *base, &known[..i], LoadMode::Lenient)?;
+    }
+    history.extend(repository, tip, known, LoadMode::Strict)?;
+    Ok(history.transactions)
+}
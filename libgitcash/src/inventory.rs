@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, FixedOffset};
+use git2::{Commit, Oid};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{
+    error::Error,
+    record::{convert_time, serde_oid},
+    transaction::{extract_data, extract_title, AccountType, RESTOCK_PREFIX},
+    Repo,
+};
+
+/// Products that were added to (or, if negative, removed from) the stock
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub struct Restock {
+    /// EAN of the product
+    pub ean: u64,
+    /// Number of items
+    pub quantity: i32,
+}
+
+/// A restock together with the commit it was loaded from
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RestockRecord {
+    /// ID of the commit containing the restock
+    #[serde(with = "serde_oid")]
+    pub id: Oid,
+    /// Commit time (in the committer's timezone)
+    pub time: DateTime<FixedOffset>,
+    /// The description in the first line of the commit message (without prefix)
+    pub title: String,
+    /// The parsed restock
+    pub restock: Restock,
+}
+
+impl RestockRecord {
+    pub(crate) fn new(commit: &Commit, restock: Restock) -> Self {
+        Self {
+            id: commit.id(),
+            time: convert_time(commit.time()),
+            title: extract_title(commit.message().unwrap_or_default(), RESTOCK_PREFIX).to_string(),
+            restock,
+        }
+    }
+}
+
+/// Extract a restock from a commit message
+pub(crate) fn extract_restock(commit_message: &str) -> Result<Restock, Error> {
+    toml::from_str(&extract_data(commit_message))
+        .map_err(|e| Error::TransactionParseError(format!("Invalid TOML restock data: {}", e)))
+}
+
+/// The current stock of a product
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StockLevel {
+    /// EAN of the product
+    pub ean: u64,
+    /// Name of the product (`None` if it is not in the catalog)
+    pub name: Option<String>,
+    /// Total number of restocked items
+    pub restocked: i64,
+    /// Total number of sold items
+    pub sold: i64,
+    /// Stock level at or below which the product is running low
+    pub min_stock: Option<u32>,
+}
+
+impl StockLevel {
+    /// Return the number of items in stock
+    pub fn stock(&self) -> i64 {
+        self.restocked - self.sold
+    }
+
+    /// Return whether the stock is at or below the minimum (or empty, if no
+    /// minimum is configured)
+    pub fn is_low(&self) -> bool {
+        self.stock() <= i64::from(self.min_stock.unwrap_or(0))
+    }
+}
+
+impl Repo {
+    /// Return all restocks from oldest to newest
+    pub fn restocks(&self) -> &[RestockRecord] {
+        &self.restocks
+    }
+
+    /// Calculate the stock of every product, ordered by EAN
+    ///
+    /// Every non-reverted transaction to a point of sale with an EAN in its
+    /// meta data counts as one sold item. Products that are not in the
+    /// catalog but were restocked or sold are included as well.
+    pub fn inventory(&self) -> Vec<StockLevel> {
+        let mut levels = BTreeMap::new();
+        for product in &self.catalog.products {
+            self.stock_level(&mut levels, product.ean);
+        }
+        for record in &self.restocks {
+            self.stock_level(&mut levels, record.restock.ean).restocked +=
+                i64::from(record.restock.quantity);
+        }
+        for record in self.transactions.iter().filter(|r| !r.is_reverted()) {
+            let transaction = &record.transaction;
+            if transaction.to.account_type != AccountType::PointOfSale {
+                continue;
+            }
+            if let Some(ean) = transaction.meta.as_ref().and_then(|meta| meta.ean) {
+                self.stock_level(&mut levels, ean).sold += 1;
+            }
+        }
+        levels.into_values().collect()
+    }
+
+    /// Return the stock level of a product, creating an empty one if needed
+    fn stock_level<'a>(
+        &self,
+        levels: &'a mut BTreeMap<u64, StockLevel>,
+        ean: u64,
+    ) -> &'a mut StockLevel {
+        levels.entry(ean).or_insert_with(|| {
+            let product = self.catalog.lookup(ean);
+            StockLevel {
+                ean,
+                name: product.map(|product| product.name.clone()),
+                restocked: 0,
+                sold: 0,
+                min_stock: product.and_then(|product| product.min_stock),
+            }
+        })
+    }
+
+    /// Record that the specified number of items of a product were added to
+    /// the stock (or removed, if the quantity is negative)
+    pub fn restock(&mut self, ean: u64, quantity: i32) -> Result<(), Error> {
+        let product = self
+            .catalog
+            .lookup(ean)
+            .ok_or_else(|| Error::ValidationError(format!("Unknown EAN: {}", ean)))?;
+        if quantity == 0 {
+            return Err(Error::ValidationError(
+                "Restock quantity may not be zero".into(),
+            ));
+        }
+
+        let restock = Restock { ean, quantity };
+        let summary = format!("{}{} x {}", RESTOCK_PREFIX, quantity, product.name);
+        debug!("Creating commit: {}", &summary);
+        let restock_toml = toml::to_string(&restock)
+            .map_err(|e| Error::TransactionSerializeError(e.to_string()))?;
+        let commit_message = format!("{}\n\n---\n{}\n---", &summary, restock_toml.trim());
+        let commit = self.commit(&commit_message)?;
+        self.restocks.push(RestockRecord::new(
+            &self.repository.find_commit(commit)?,
+            restock,
+        ));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_utils::TestRepo, Product, Repo};
+
+    const EAN: u64 = 7610867035003;
+
+    #[test]
+    fn test_inventory() {
+        let test_repo = TestRepo::new();
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        repo.add_product(Product {
+            ean: EAN,
+            name: "Vivi Kola 33cl".into(),
+            price: 250,
+            class: None,
+            min_stock: Some(5),
+        })
+        .unwrap();
+        repo.restock(EAN, 6).unwrap();
+        assert!(repo.restock(96385074, 6).is_err());
+        let purchase = format!(
+            "from = \"user:a\"\nto = \"pos:fridge\"\namount = 250\n\n[meta]\nean = {}",
+            EAN
+        );
+        test_repo.commit_transaction("Purchase", &purchase);
+        test_repo.commit_transaction("Purchase", &purchase);
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        assert_eq!(repo.restocks()[0].title, "6 x Vivi Kola 33cl");
+        let inventory = repo.inventory();
+        assert_eq!(inventory.len(), 1);
+        assert_eq!((inventory[0].restocked, inventory[0].sold), (6, 2));
+        assert_eq!(inventory[0].stock(), 4);
+        assert!(inventory[0].is_low());
+    }
+}
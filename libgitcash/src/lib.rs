@@ -1,32 +1,98 @@
 use std::{
+    cell::OnceCell,
     collections::{HashMap, HashSet},
     path::Path,
 };
 
-use git2::{Signature, Sort};
+use git2::Signature;
 use tracing::debug;
 
+mod account_history;
+mod balance;
+mod cache;
+mod catalog;
 mod config;
 mod error;
+mod history;
+mod inventory;
+mod money;
+mod record;
+mod registry;
+mod revert;
+mod statistics;
+mod sync;
+#[cfg(test)]
+mod test_utils;
 mod transaction;
+mod validation;
 
-use crate::{error::Error, transaction::extract_transaction};
+use crate::{
+    cache::{load_cached_history, verify_cache},
+    history::History,
+    transaction::{extract_title, REVERT_PREFIX, REVERT_REFERENCE_PREFIX, TRANSACTION_PREFIX},
+};
 
 pub use crate::{
-    config::{Currency, RepoConfig},
-    transaction::{Account, AccountType, Transaction},
+    account_history::HistoryEntry,
+    balance::{compute_balances, CurrencyBalances},
+    catalog::{validate_ean, Catalog, Product},
+    config::{Currency, CurrencyFormat, Limits, NegativeStyle, RepoConfig, SymbolPosition},
+    error::Error,
+    history::{load_new_transactions, load_transactions, Diagnostic, LoadMode},
+    inventory::{Restock, RestockRecord, StockLevel},
+    money::Money,
+    record::{CommitSignature, TransactionRecord},
+    registry::{AccountInfo, Registry},
+    statistics::{AggregateRow, Aggregation, AggregationQuery, Period},
+    sync::{Integration, SyncResult},
+    transaction::{Account, AccountType, Exchange, Posting, Transaction, TransactionMeta},
+    validation::{find_violations, Rule, Violation},
 };
 
+/// Options for opening a repository
+#[derive(Debug, Default, Clone)]
+pub struct OpenOptions {
+    /// Cache parsed transactions in the `.git` directory, so that only new
+    /// commits need to be parsed the next time the repository is opened
+    pub cache: bool,
+    /// Whether invalid commits abort loading or are skipped
+    pub mode: LoadMode,
+}
+
+/// Options for creating a transaction
+#[derive(Debug, Default, Clone)]
+pub struct TransactionOptions {
+    /// Allow the transaction even if it brings an account below the minimum
+    /// balance configured in the `[limits]` section of `gitcash.toml`
+    pub allow_overdraft: bool,
+}
+
 /// A GitCash repository and all its transactions
 pub struct Repo {
     repository: git2::Repository,
+    /// The commit up to which the history was loaded
+    loaded_head: git2::Oid,
+    options: OpenOptions,
     config: RepoConfig,
-    transactions: Vec<Transaction>,
+    catalog: Catalog,
+    registry: Registry,
+    transactions: Vec<TransactionRecord>,
+    /// Balances of all accounts, computed on first use (or loaded from the
+    /// cache)
+    balances: OnceCell<CurrencyBalances>,
+    restocks: Vec<RestockRecord>,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Repo {
     /// Open a GitCash repository at the specified path and parse all transactions
     pub fn open(repo_path: &Path) -> Result<Self, Error> {
+        Self::open_with_options(repo_path, OpenOptions::default())
+    }
+
+    /// Open a GitCash repository at the specified path using the specified
+    /// options and parse all transactions
+    pub fn open_with_options(repo_path: &Path, options: OpenOptions) -> Result<Self, Error> {
         // Open git repo
         tracing::debug!("Loading repository at {:?}", repo_path);
         let repo = match git2::Repository::open(repo_path) {
@@ -34,89 +100,786 @@ impl Repo {
             Err(e) => return Err(Error::RepoError(format!("Failed to open repo: {}", e))),
         };
 
-        // Read config
+        // Read config, product catalog and account registry
         let config = RepoConfig::load(repo_path)?;
+        let catalog = Catalog::load(repo_path)?;
+        let registry = Registry::load(repo_path)?;
+
+        // Extract transactions
+        let head = repo.head()?.peel_to_commit()?.id();
+        let mut repo = Repo {
+            repository: repo,
+            loaded_head: head,
+            options,
+            config,
+            catalog,
+            registry,
+            transactions: Vec::new(),
+            balances: OnceCell::new(),
+            restocks: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        repo.load_history(head)?;
 
-        // Traverse commits from oldest to newest, extract transactions
-        let mut revwalk = repo.revwalk()?;
-        revwalk.push_head()?;
-        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
-        let mut transactions = Vec::new();
-        for commit_oid in revwalk {
-            let commit = repo.find_commit(commit_oid?)?;
-            let message = match commit.message_raw() {
-                Some(msg) => msg,
-                None => continue,
+        Ok(repo)
+    }
+
+    /// Load transactions up to the specified commit (using the cache if enabled)
+    fn load_history(&mut self, head: git2::Oid) -> Result<(), Error> {
+        let (history, balances) = if self.options.cache {
+            load_cached_history(
+                &self.repository,
+                head,
+                self.options.mode,
+                &self.config.currency.code,
+            )?
+        } else {
+            (
+                History::load(&self.repository, head, self.options.mode)?,
+                None,
+            )
+        };
+        self.loaded_head = head;
+        self.transactions = history.transactions;
+        self.balances = OnceCell::new();
+        if let Some(balances) = balances {
+            self.balances.get_or_init(|| balances);
+        }
+        self.restocks = history.restocks;
+        self.diagnostics = history.diagnostics;
+        Ok(())
+    }
+
+    /// Compare the transaction cache against a full rebuild
+    ///
+    /// Returns the ID of the last commit in the cache if it is valid.
+    pub fn verify_cache(&self) -> Result<git2::Oid, Error> {
+        verify_cache(
+            &self.repository,
+            self.options.mode,
+            &self.config.currency.code,
+        )
+    }
+
+    /// Reload config, catalog, registry and transactions, e.g. after the
+    /// history was changed
+    pub fn reload(&mut self) -> Result<(), Error> {
+        let head = {
+            let head = self.repository.head()?.peel_to_commit()?;
+            (self.config, self.catalog, self.registry) = match self.repository.workdir() {
+                Some(workdir) => (
+                    RepoConfig::load(workdir)?,
+                    Catalog::load(workdir)?,
+                    Registry::load(workdir)?,
+                ),
+                None => (
+                    RepoConfig::from_commit(&self.repository, &head)?,
+                    Catalog::from_commit(&self.repository, &head)?,
+                    Registry::from_commit(&self.repository, &head)?,
+                ),
             };
-            if !message.starts_with("Transaction: ") {
-                continue;
-            }
-            debug!("Processing commit {}", commit.id());
-            let transaction = extract_transaction(message)?;
-            transactions.push(transaction);
+            head.id()
+        };
+        self.load_history(head)?;
+        Ok(())
+    }
+
+    /// Reload if HEAD was moved since the history was loaded (e.g. by
+    /// another process), return whether anything was reloaded
+    pub fn reload_if_changed(&mut self) -> Result<bool, Error> {
+        if self.head()? == self.loaded_head {
+            return Ok(false);
         }
+        self.reload()?;
+        Ok(true)
+    }
 
-        Ok(Repo {
-            repository: repo,
-            config,
-            transactions,
-        })
+    /// Return all transactions (including reverted ones) from oldest to newest
+    pub fn transactions(&self) -> &[TransactionRecord] {
+        &self.transactions
+    }
+
+    /// Return the ID of the current HEAD commit
+    pub fn head(&self) -> Result<git2::Oid, Error> {
+        Ok(self.repository.head()?.peel_to_commit()?.id())
+    }
+
+    /// Return whether the specified commit is part of the history of HEAD
+    ///
+    /// This is not the case for unknown commits, e.g. after the history was
+    /// rewritten.
+    pub fn contains_commit(&self, commit: git2::Oid) -> Result<bool, Error> {
+        let head = self.head()?;
+        Ok(commit == head
+            || self
+                .repository
+                .graph_descendant_of(head, commit)
+                .unwrap_or(false))
+    }
+
+    /// Return all commits that are reachable from HEAD but not from the
+    /// specified commit
+    fn commits_since(&self, commit: git2::Oid) -> Result<HashSet<git2::Oid>, Error> {
+        let mut revwalk = self.repository.revwalk()?;
+        revwalk.push(self.head()?)?;
+        revwalk.hide(commit)?;
+        Ok(revwalk.collect::<Result<HashSet<_>, _>>()?)
     }
 
-    /// Return set of all acounts
+    /// Return all transactions from commits that are reachable from HEAD but
+    /// not from the specified commit, from oldest to newest
+    pub fn transactions_since(&self, commit: git2::Oid) -> Result<Vec<&TransactionRecord>, Error> {
+        let new_commits = self.commits_since(commit)?;
+        Ok(self
+            .transactions
+            .iter()
+            .filter(|record| new_commits.contains(&record.id))
+            .collect())
+    }
+
+    /// Return all transactions that were reverted by commits that are
+    /// reachable from HEAD but not from the specified commit
+    pub fn reverts_since(&self, commit: git2::Oid) -> Result<Vec<&TransactionRecord>, Error> {
+        let new_commits = self.commits_since(commit)?;
+        Ok(self
+            .transactions
+            .iter()
+            .filter(|record| {
+                record
+                    .reverted_by
+                    .is_some_and(|revert| new_commits.contains(&revert))
+            })
+            .collect())
+    }
+
+    /// Return all commits that were skipped while loading in lenient mode
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Return all non-reverted transactions in the history that break a rule
+    pub fn violations(&self) -> Vec<Violation> {
+        find_violations(&self.transactions, &self.config)
+    }
+
+    /// Return set of all acounts (used in transactions or registered, see
+    /// [`Repo::account_info`] for their metadata)
     pub fn accounts(&self) -> HashSet<Account> {
         self.transactions
             .iter()
+            .map(|record| &record.transaction)
             .flat_map(|t| [t.from.clone(), t.to.clone()])
+            .chain(
+                self.registry
+                    .accounts
+                    .iter()
+                    .map(|info| info.account.clone()),
+            )
             .collect()
     }
 
-    /// Return all accounts and their balances
-    pub fn balances(&self) -> HashMap<Account, i32> {
-        let mut accounts = HashMap::new();
-        for transaction in &self.transactions {
-            let source = accounts.entry(transaction.from.clone()).or_default();
-            *source -= transaction.amount;
-            let destination = accounts.entry(transaction.to.clone()).or_default();
-            *destination += transaction.amount;
+    /// Return all accounts and their balances, by currency code
+    ///
+    /// Reverted transactions are not taken into account. Balances are
+    /// accumulated as `i64`, if they still overflow, an error pointing at the
+    /// offending transaction is returned.
+    pub fn balances(&self) -> Result<CurrencyBalances, Error> {
+        if let Some(balances) = self.balances.get() {
+            return Ok(balances.clone());
         }
-        accounts
+        let balances = compute_balances(&self.transactions, &self.config.currency.code)?;
+        Ok(self.balances.get_or_init(|| balances).clone())
     }
 
-    /// Convert a floating-point currency amount into an integer based value.
-    pub fn convert_amount(&self, amount: f32) -> i32 {
-        let amount = amount * self.config.currency.divisor as f32;
-        amount as i32
+    /// Return all accounts and their balances in the specified currency
+    pub fn balances_in(&self, currency: &str) -> Result<HashMap<Account, i64>, Error> {
+        if self.config.find_currency(Some(currency)).is_none() {
+            return Err(Error::ValidationError(format!(
+                "Unknown currency: {}",
+                currency
+            )));
+        }
+        Ok(self.balances()?.remove(currency).unwrap_or_default())
     }
 
-    pub fn create_transaction(&mut self, transaction: Transaction) -> Result<(), Error> {
-        let summary = transaction.summary(&self.config);
-        debug!("Creating commit: {}", &summary);
+    /// Return the repo config
+    pub fn config(&self) -> &RepoConfig {
+        &self.config
+    }
+
+    /// Parse a decimal amount in the primary currency of this repository
+    pub fn parse_amount(&self, input: &str) -> Result<Money, Error> {
+        Money::parse(input, &self.config.currency)
+    }
+
+    /// Parse a decimal amount in the specified currency
+    pub fn parse_amount_in(&self, input: &str, currency: &str) -> Result<Money, Error> {
+        let currency = self
+            .config
+            .find_currency(Some(currency))
+            .ok_or_else(|| Error::ValidationError(format!("Unknown currency: {}", currency)))?;
+        Money::parse(input, currency)
+    }
+
+    /// Validate a transaction and return the commit message that
+    /// [`Repo::create_transaction`] would use for it
+    ///
+    /// Transactions that break a rule are rejected.
+    pub fn transaction_message(&self, transaction: &Transaction) -> Result<String, Error> {
+        transaction.check_rules(&self.config)?;
 
         // Encode transaction
-        let transaction_toml = toml::to_string(&transaction)
+        let summary = transaction.summary(&self.config);
+        let transaction_toml = toml::to_string(transaction)
             .map_err(|e| Error::TransactionSerializeError(e.to_string()))?;
-        let commit_message = format!("{}\n\n---\n{}\n---", &summary, transaction_toml.trim());
+        Ok(format!(
+            "{}\n\n---\n{}\n---",
+            summary,
+            transaction_toml.trim()
+        ))
+    }
+
+    /// Ensure that a transaction doesn't bring any account below its minimum
+    /// balance (see [`Limits`])
+    ///
+    /// Only amounts in the primary currency are limited. Accounts that are
+    /// already below their limit may still receive money.
+    pub fn check_limits(&self, transaction: &Transaction) -> Result<(), Error> {
+        self.check_limits_after(&[], transaction)
+    }
+
+    /// Same as [`Repo::check_limits`], but as if the `pending` transactions
+    /// had been created before (e.g. the previous rows of an import)
+    pub fn check_limits_after(
+        &self,
+        pending: &[&Transaction],
+        transaction: &Transaction,
+    ) -> Result<(), Error> {
+        let mut balances = self.balances_in(&self.config.currency.code)?;
+        for pending in pending {
+            self.apply_postings(&mut balances, pending, false)?;
+        }
+        self.apply_postings(&mut balances, transaction, true)
+    }
+
+    /// Apply the postings of a transaction in the primary currency to the
+    /// balances, optionally checking the limit of every debited account
+    fn apply_postings(
+        &self,
+        balances: &mut HashMap<Account, i64>,
+        transaction: &Transaction,
+        check: bool,
+    ) -> Result<(), Error> {
+        for posting in transaction.postings() {
+            let in_primary_currency = posting
+                .currency
+                .map_or(true, |code| code == self.config.currency.code);
+            if !in_primary_currency || posting.amount == 0 {
+                continue;
+            }
+            // A negative amount moves money from `to` to `from`
+            let (sender, receiver) = if posting.amount < 0 {
+                (posting.to, posting.from)
+            } else {
+                (posting.from, posting.to)
+            };
+            let amount = posting.amount.abs();
+            let balance = balances.entry(sender.clone()).or_default();
+            if let Some(limit) = self.config.limits.min_balance(sender).filter(|_| check) {
+                if *balance - amount < limit {
+                    return Err(Error::CreditLimitError {
+                        account: sender.clone(),
+                        balance: *balance,
+                        limit,
+                    });
+                }
+            }
+            *balance -= amount;
+            *balances.entry(receiver.clone()).or_default() += amount;
+        }
+        Ok(())
+    }
+
+    /// Create a new transaction and commit it to the repository
+    ///
+    /// Transactions that break a rule or a credit limit are rejected.
+    pub fn create_transaction(&mut self, transaction: Transaction) -> Result<(), Error> {
+        self.create_transaction_with_options(transaction, TransactionOptions::default())
+    }
+
+    /// Create a new transaction using the specified options and commit it to
+    /// the repository
+    pub fn create_transaction_with_options(
+        &mut self,
+        transaction: Transaction,
+        options: TransactionOptions,
+    ) -> Result<(), Error> {
+        let commit_message = self.transaction_message(&transaction)?;
+        if !options.allow_overdraft {
+            self.check_limits(&transaction)?;
+        }
+        let title = extract_title(&commit_message, TRANSACTION_PREFIX).to_string();
+        debug!("Creating commit: {}", &title);
+        let commit = self.commit(&commit_message)?;
+
+        // Store transaction
+        self.transactions.push(TransactionRecord::new(
+            &self.repository.find_commit(commit)?,
+            title,
+            transaction,
+        ));
+        self.balances.take();
+
+        Ok(())
+    }
+
+    /// Revert a transaction by committing a revert that references it
+    ///
+    /// Transactions that were already reverted are rejected. Returns the ID
+    /// of the revert commit.
+    pub fn revert_transaction(&mut self, id: git2::Oid) -> Result<git2::Oid, Error> {
+        let index = self
+            .transactions
+            .iter()
+            .position(|record| record.id == id)
+            .ok_or_else(|| {
+                Error::RevertError(format!("Commit {} is not a known transaction", id))
+            })?;
+        let record = &self.transactions[index];
+        if let Some(reverted_by) = record.reverted_by {
+            return Err(Error::RevertError(format!(
+                "Transaction {} was already reverted by {}",
+                id, reverted_by
+            )));
+        }
+
+        let commit_message = format!(
+            "{}{}\n\n{}{}.",
+            REVERT_PREFIX, record.title, REVERT_REFERENCE_PREFIX, id
+        );
+        debug!("Creating commit: {}", &commit_message);
+        let commit = self.commit(&commit_message)?;
+        if let Some(record) = self.transactions.iter_mut().find(|record| record.id == id) {
+            record.reverted_by = Some(commit);
+        }
+        self.balances.take();
+        Ok(commit)
+    }
 
+    /// Commit the current index on top of HEAD
+    ///
+    /// If HEAD was moved since the history was loaded (e.g. by another
+    /// process), the history is reloaded first, so that the new commit can be
+    /// added to it.
+    fn commit(&mut self, message: &str) -> Result<git2::Oid, Error> {
         // Create signature (for both committer and author)
-        let sig = Signature::now("GitCash CLI", "gitcash@coredump.ch")?;
+        let sig = signature()?;
+
+        if self.head()? != self.loaded_head {
+            self.reload()?;
+        }
 
         // Create tree object
-        let head = self
-            .repository
-            .find_commit(self.repository.head()?.target().unwrap())?;
+        let head = self.repository.head()?.peel_to_commit()?;
         let tree_id = self.repository.index()?.write_tree()?;
         let tree = self.repository.find_tree(tree_id)?;
 
         // Create commit
-        let commit =
-            self.repository
-                .commit(Some("HEAD"), &sig, &sig, &commit_message, &tree, &[&head])?;
+        let commit = self
+            .repository
+            .commit(Some("HEAD"), &sig, &sig, message, &tree, &[&head])?;
+        debug!("Created commit: {commit}");
+        self.loaded_head = commit;
+        Ok(commit)
+    }
+}
 
-        // Store transaction
-        self.transactions.push(transaction);
+/// Return the signature used for commits created by GitCash
+pub(crate) fn signature() -> Result<Signature<'static>, Error> {
+    Ok(Signature::now("GitCash CLI", "gitcash@coredump.ch")?)
+}
 
-        debug!("Created commit: {commit}");
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::{self, TestRepo},
+        Account, Error, LoadMode, OpenOptions, Repo, Rule, Transaction, TransactionOptions,
+    };
+
+    const DEPOSIT: &str = "from = \"source:cash\"\nto = \"user:danilo\"\namount = 2000";
+    const PURCHASE: &str = "from = \"user:danilo\"\nto = \"pos:fridge\"\namount = 250";
+
+    fn balance(repo: &Repo, account: &str) -> i64 {
+        let account = Account::try_from(account.to_string()).unwrap();
+        repo.balances_in(&repo.config().currency.code)
+            .unwrap()
+            .get(&account)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_transaction_records() {
+        let test_repo = TestRepo::new();
+        let deposit = test_repo.commit_transaction("Deposit", DEPOSIT);
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        let records = repo.transactions();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id, deposit);
+        assert_eq!(records[0].title, "Deposit");
+        assert_eq!(records[0].author.name, "Test");
+        assert_eq!(records[0].committer.email, "test@example.com");
+        assert_eq!(
+            records[0].time.timestamp(),
+            test_repo
+                .repo
+                .find_commit(deposit)
+                .unwrap()
+                .time()
+                .seconds()
+        );
+    }
+
+    #[test]
+    fn test_transactions_since() {
+        let test_repo = TestRepo::new();
+        let deposit = test_repo.commit_transaction("Deposit", DEPOSIT);
+        let purchase = test_repo.commit_transaction("Purchase", PURCHASE);
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        let new = repo.transactions_since(deposit).unwrap();
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].id, purchase);
+        assert!(repo.transactions_since(purchase).unwrap().is_empty());
+        assert!(repo.contains_commit(deposit).unwrap());
+        assert!(!repo.contains_commit(git2::Oid::zero()).unwrap());
+    }
+
+    #[test]
+    fn test_reverts_since() {
+        let test_repo = TestRepo::new();
+        let deposit = test_repo.commit_transaction("Deposit", DEPOSIT);
+        let purchase = test_repo.commit_transaction("Purchase", PURCHASE);
+
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        let head = repo.head().unwrap();
+        test_repo.commit(&format!(
+            "Revert: Purchase\n\nThis reverts commit {}.",
+            purchase
+        ));
+        assert!(repo.reload_if_changed().unwrap());
+        let reverted = repo.reverts_since(head).unwrap();
+        assert_eq!(reverted.len(), 1);
+        assert_eq!(reverted[0].id, purchase);
+        assert_eq!(repo.reverts_since(deposit).unwrap().len(), 1);
+        assert!(repo.reverts_since(repo.head().unwrap()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reload_if_changed() {
+        let test_repo = TestRepo::new();
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        assert!(!repo.reload_if_changed().unwrap());
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        assert!(repo.reload_if_changed().unwrap());
+        assert_eq!(balance(&repo, "user:danilo"), 2000);
+
+        // Own commits don't require a reload
+        repo.create_transaction(Transaction {
+            from: Account::user("danilo").unwrap(),
+            to: Account::point_of_sale("fridge").unwrap(),
+            amount: 250,
+            currency: None,
+            description: None,
+            meta: None,
+            exchange: None,
+        })
+        .unwrap();
+        assert!(!repo.reload_if_changed().unwrap());
+        assert_eq!(balance(&repo, "user:danilo"), 1750);
+    }
+
+    #[test]
+    fn test_commit_after_external_commit() {
+        let test_repo = TestRepo::new();
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        let mut other = Repo::open(test_repo.path()).unwrap();
+        other
+            .create_transaction(toml::from_str(DEPOSIT).unwrap())
+            .unwrap();
+
+        // The commit of the other handle is loaded before committing
+        repo.create_transaction(toml::from_str(PURCHASE).unwrap())
+            .unwrap();
+        assert_eq!(repo.transactions().len(), 2);
+        assert!(!repo.reload_if_changed().unwrap());
+        assert_eq!(balance(&repo, "user:danilo"), 1750);
+
+        // Transactions are only known after a reload, reverts are picked up
+        // as well
+        let id = repo.transactions()[1].id;
+        other.revert_transaction(id).unwrap_err();
+        assert!(other.reload_if_changed().unwrap());
+        other.revert_transaction(id).unwrap();
+        repo.create_transaction(toml::from_str(PURCHASE).unwrap())
+            .unwrap();
+        assert_eq!(balance(&repo, "user:danilo"), 1750);
+    }
+
+    #[test]
+    fn test_lenient_mode() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        let broken = test_repo.commit_transaction(
+            "Broken",
+            "from = \"user:danilo\"\nto = \"pos:fridge\"\namount = \"2.50\"",
+        );
+        test_repo.commit_transaction("Purchase", PURCHASE);
+        assert!(Repo::open(test_repo.path()).is_err());
+
+        let options = OpenOptions {
+            mode: LoadMode::Lenient,
+            ..Default::default()
+        };
+        let repo = Repo::open_with_options(test_repo.path(), options).unwrap();
+        assert_eq!(repo.transactions().len(), 2);
+        assert_eq!(balance(&repo, "user:danilo"), 1750);
+        let diagnostics = repo.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].commit, broken);
+        assert_eq!(diagnostics[0].line.as_deref(), Some("amount = \"2.50\""));
+    }
+
+    #[test]
+    fn test_violations() {
+        let test_repo = TestRepo::new();
+        let drain = test_repo.commit_transaction(
+            "Drain",
+            "from = \"pos:fridge\"\nto = \"user:danilo\"\namount = 100",
+        );
+
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        let violations = repo.violations();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].commit, drain);
+        assert_eq!(violations[0].rule, Rule::PointOfSaleCannotSend);
+
+        let result = repo.create_transaction(Transaction {
+            from: Account::user("danilo").unwrap(),
+            to: Account::source("cash").unwrap(),
+            amount: 100,
+            currency: None,
+            description: None,
+            meta: None,
+            exchange: None,
+        });
+        assert!(result.is_err());
+        assert_eq!(repo.transactions().len(), 1);
+    }
+
+    #[test]
+    fn test_balances_do_not_overflow() {
+        let test_repo = TestRepo::new();
+        let data = "from = \"source:cash\"\nto = \"user:danilo\"\namount = 2147483647";
+        test_repo.commit_transaction("Deposit", data);
+        test_repo.commit_transaction("Deposit", data);
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        assert_eq!(balance(&repo, "user:danilo"), 2 * i64::from(i32::MAX));
+        assert_eq!(balance(&repo, "source:cash"), -2 * i64::from(i32::MAX));
+    }
+
+    #[test]
+    fn test_exchange_balances() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        let data = "from = \"user:danilo\"\nto = \"user:bank\"\namount = 1000\n\n\
+                    [exchange]\ncurrency = \"EUR\"\namount = 950\nrate = \"0.95\"";
+        test_repo.commit_transaction("Exchange", data);
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        let balances = repo.balances().unwrap();
+        let danilo = Account::user("danilo").unwrap();
+        let bank = Account::user("bank").unwrap();
+        assert_eq!(balances["CHF"][&danilo], balance(&repo, "user:danilo"));
+        assert_eq!(balances["CHF"][&bank], 1000);
+        assert_eq!(balances["EUR"][&danilo], 950);
+        assert_eq!(balances["EUR"][&bank], -950);
+        assert!(repo.balances_in("EUR").is_err());
+    }
+
+    #[test]
+    fn test_credit_limit() {
+        let test_repo = TestRepo::new();
+        let config = format!("{}\n[limits]\nuser = -300\n", test_utils::CONFIG);
+        std::fs::write(test_repo.path().join("gitcash.toml"), config).unwrap();
+        test_repo.commit_transaction("Purchase", PURCHASE);
+
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        let purchase = || Transaction {
+            from: Account::user("danilo").unwrap(),
+            to: Account::point_of_sale("fridge").unwrap(),
+            amount: 100,
+            currency: None,
+            description: None,
+            meta: None,
+            exchange: None,
+        };
+        let err = repo.create_transaction(purchase()).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::CreditLimitError {
+                    balance: -250,
+                    limit: -300,
+                    ..
+                }
+            ),
+            "{}",
+            err
+        );
+        assert_eq!(balance(&repo, "user:danilo"), -250);
+
+        let options = TransactionOptions {
+            allow_overdraft: true,
+        };
+        repo.create_transaction_with_options(purchase(), options)
+            .unwrap();
+        assert_eq!(balance(&repo, "user:danilo"), -350);
+    }
+
+    #[test]
+    fn test_credit_limit_negative_amount() {
+        let test_repo = TestRepo::new();
+        let config = format!("{}\n[limits]\nuser = -300\n", test_utils::CONFIG);
+        std::fs::write(test_repo.path().join("gitcash.toml"), config).unwrap();
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+
+        // A negative transfer from danilo to rnestler debits rnestler
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        let transfer = |amount| Transaction {
+            from: Account::user("danilo").unwrap(),
+            to: Account::user("rnestler").unwrap(),
+            amount,
+            currency: None,
+            description: None,
+            meta: None,
+            exchange: None,
+        };
+        let err = repo.create_transaction(transfer(-400)).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                Error::CreditLimitError {
+                    account,
+                    balance: 0,
+                    limit: -300,
+                } if account.name == "rnestler"
+            ),
+            "{}",
+            err
+        );
+        repo.create_transaction(transfer(-300)).unwrap();
+        assert_eq!(balance(&repo, "user:rnestler"), -300);
+        assert_eq!(balance(&repo, "user:danilo"), 2300);
+    }
+
+    #[test]
+    fn test_credit_limit_after_pending() {
+        let test_repo = TestRepo::new();
+        let config = format!("{}\n[limits]\nuser = -300\n", test_utils::CONFIG);
+        std::fs::write(test_repo.path().join("gitcash.toml"), config).unwrap();
+        test_repo.commit_transaction("Purchase", PURCHASE);
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        let purchase = toml::from_str::<Transaction>(PURCHASE).unwrap();
+        let deposit = toml::from_str::<Transaction>(DEPOSIT).unwrap();
+        assert!(repo.check_limits(&purchase).is_err());
+        repo.check_limits_after(&[&deposit], &purchase).unwrap();
+        // 1750 CHF after the deposit, -250 CHF after eight more purchases
+        let mut pending = vec![&deposit];
+        pending.extend([&purchase; 8]);
+        assert!(repo.check_limits_after(&pending, &purchase).is_err());
+    }
+
+    #[test]
+    fn test_revert_by_message() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        test_repo.commit_transaction("Purchase", PURCHASE);
+        test_repo.commit(&format!("Revert: Purchase\n\n---\n{}\n---", PURCHASE));
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        assert_eq!(balance(&repo, "user:danilo"), 2000);
+        assert_eq!(balance(&repo, "pos:fridge"), 0);
+    }
+
+    #[test]
+    fn test_revert_with_different_title() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("User danilo pays 2.50 CHF", PURCHASE);
+        test_repo.commit(&format!(
+            "Revert: User danilo pays CHF 2.50\n\n---\n{}\n---",
+            PURCHASE
+        ));
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        assert!(repo.transactions()[0].is_reverted());
+    }
+
+    #[test]
+    fn test_revert_by_reference() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Purchase", PURCHASE);
+        let second = test_repo.commit_transaction("Purchase", PURCHASE);
+        test_repo.commit(&format!(
+            "Revert: Purchase\n\nThis reverts commit {}.",
+            &second.to_string()[..10]
+        ));
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        assert!(repo.transactions()[1].is_reverted());
+        assert!(!repo.transactions()[0].is_reverted());
+        assert_eq!(balance(&repo, "user:danilo"), -250);
+    }
+
+    #[test]
+    fn test_revert_twice() {
+        let test_repo = TestRepo::new();
+        let purchase = test_repo.commit_transaction("Purchase", PURCHASE);
+        let revert = format!("Revert: Purchase\n\nThis reverts commit {}.", purchase);
+        test_repo.commit(&revert);
+        test_repo.commit(&revert);
+
+        let err = Repo::open(test_repo.path()).err().unwrap();
+        assert!(err.to_string().contains("already reverted"), "{}", err);
+    }
+
+    #[test]
+    fn test_revert_transaction() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Deposit", DEPOSIT);
+        let purchase = test_repo.commit_transaction("Purchase", PURCHASE);
+
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        let revert = repo.revert_transaction(purchase).unwrap();
+        assert_eq!(balance(&repo, "user:danilo"), 2000);
+        assert!(repo.revert_transaction(purchase).is_err());
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        assert_eq!(repo.transactions()[1].reverted_by, Some(revert));
+        assert_eq!(balance(&repo, "user:danilo"), 2000);
+    }
+
+    #[test]
+    fn test_revert_unknown() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction("Purchase", PURCHASE);
+        let revert = test_repo.commit(&format!("Revert: Deposit\n\n---\n{}\n---", DEPOSIT));
+
+        let err = Repo::open(test_repo.path()).err().unwrap();
+        assert!(err.to_string().contains("does not match"), "{}", err);
+        assert!(matches!(err, Error::CommitError(commit, _) if commit == revert));
     }
 }
@@ -0,0 +1,245 @@
+use crate::{config::Currency, error::Error, transaction::Exchange};
+
+/// An exact amount of money, stored as integer in the minor unit of a
+/// currency (e.g. in cents)
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct Money(i32);
+
+impl Money {
+    pub fn from_minor_units(amount: i32) -> Self {
+        Self(amount)
+    }
+
+    /// Return the amount in minor units, as used in transactions
+    pub fn minor_units(&self) -> i32 {
+        self.0
+    }
+
+    /// Parse a decimal amount like `2.50`, `2.5 CHF` or `-1.20`
+    ///
+    /// The currency code (or the configured symbol) is optional and may be
+    /// placed before or after the number. Besides `.`, the configured decimal
+    /// separator is accepted. The number of decimal places may not exceed the
+    /// precision of the currency, and the result must fit into a transaction
+    /// amount.
+    pub fn parse(input: &str, currency: &Currency) -> Result<Self, Error> {
+        let invalid =
+            |reason: &str| Error::AmountParseError(format!("{} ({})", input.trim(), reason));
+
+        let number = strip_currency_code(input.trim(), &currency.code);
+        let number = match &currency.format.symbol {
+            Some(symbol) => strip_currency_code(number, symbol),
+            None => number,
+        };
+
+        // Sign
+        let (negative, number) = match number.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, number.strip_prefix('+').unwrap_or(number)),
+        };
+
+        // Integer and fractional part
+        let (integer, fraction) = number
+            .split_once('.')
+            .or_else(|| number.split_once(currency.format.decimal_separator.as_str()))
+            .unwrap_or((number, ""));
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(invalid("no digits"));
+        }
+        if !integer
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid("not a number"));
+        }
+        let decimals = currency.decimals();
+        if fraction.len() > decimals as usize {
+            return Err(invalid(&format!(
+                "at most {} decimal place(s) are allowed for {}",
+                decimals, currency.code
+            )));
+        }
+
+        // Convert to minor units
+        let out_of_range = || invalid("out of range");
+        let integer = match integer {
+            "" => 0,
+            digits => digits.parse::<i64>().map_err(|_| out_of_range())?,
+        };
+        let fraction = match fraction {
+            "" => 0,
+            digits => {
+                digits.parse::<i64>().map_err(|_| out_of_range())?
+                    * 10i64.pow(decimals - digits.len() as u32)
+            }
+        };
+        let amount = integer
+            .checked_mul(currency.divisor as i64)
+            .and_then(|amount| amount.checked_add(fraction))
+            .map(|amount| if negative { -amount } else { amount })
+            .ok_or_else(out_of_range)?;
+        let amount = i32::try_from(amount).map_err(|_| out_of_range())?;
+        Ok(Self(amount))
+    }
+}
+
+impl Exchange {
+    /// Create an exchange of `amount` (in minor units of the currency `from`)
+    /// into the currency `to` at the specified rate
+    ///
+    /// The rate is a decimal number of units of `to` per unit of `from`
+    /// (e.g. "0.95"). The converted amount is rounded to the nearest minor
+    /// unit of `to`.
+    pub fn at_rate(
+        amount: Money,
+        from: &Currency,
+        to: &Currency,
+        rate: &str,
+    ) -> Result<Self, Error> {
+        let invalid = || Error::ValidationError(format!("Invalid exchange rate: {}", rate));
+
+        // Parse rate as fraction
+        let (integer, fraction) = rate.trim().split_once('.').unwrap_or((rate.trim(), ""));
+        let digits = format!("{}{}", integer, fraction);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let numerator: i128 = digits.parse().map_err(|_| invalid())?;
+        let denominator = 10i128
+            .checked_pow(fraction.len() as u32)
+            .ok_or_else(invalid)?;
+        if numerator == 0 {
+            return Err(invalid());
+        }
+
+        // Convert, rounding half away from zero
+        let value = i128::from(amount.minor_units())
+            .checked_mul(numerator)
+            .and_then(|value| value.checked_mul(to.divisor as i128))
+            .ok_or_else(invalid)?;
+        let divisor = denominator * from.divisor as i128;
+        let converted = (value.abs() * 2 + divisor) / (divisor * 2) * value.signum();
+        let converted = i32::try_from(converted).map_err(|_| {
+            Error::AmountParseError(format!("Exchanged amount is out of range: {}", converted))
+        })?;
+        Ok(Self {
+            currency: to.code.clone(),
+            amount: converted,
+            rate: rate.trim().to_string(),
+        })
+    }
+}
+
+/// Strip a currency code or symbol (case-insensitive) before or after a number
+fn strip_currency_code<'a>(input: &'a str, code: &str) -> &'a str {
+    let matches_code =
+        |part: Option<&str>| part.is_some_and(|part| part.eq_ignore_ascii_case(code));
+    if let Some(split) = input.len().checked_sub(code.len()) {
+        if matches_code(input.get(split..)) {
+            return input[..split].trim_end();
+        }
+    }
+    if matches_code(input.get(..code.len())) {
+        return input[code.len()..].trim_start();
+    }
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CurrencyFormat;
+
+    fn chf() -> Currency {
+        Currency {
+            code: "CHF".into(),
+            divisor: 100,
+            format: CurrencyFormat::default(),
+        }
+    }
+
+    fn parse(input: &str) -> Result<i32, Error> {
+        Money::parse(input, &chf()).map(|money| money.minor_units())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("2.50").unwrap(), 250);
+        assert_eq!(parse("2.5 CHF").unwrap(), 250);
+        assert_eq!(parse("chf 2.3").unwrap(), 230);
+        assert_eq!(parse("0.29").unwrap(), 29);
+        assert_eq!(parse("-1.20").unwrap(), -120);
+        assert_eq!(parse("3").unwrap(), 300);
+        assert_eq!(parse(".5").unwrap(), 50);
+        assert_eq!(parse("21474836.47").unwrap(), i32::MAX);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse("").is_err());
+        assert!(parse("abc").is_err());
+        assert!(parse("1.234").is_err());
+        assert!(parse("1.2.3").is_err());
+        assert!(parse("2.50 EUR").is_err());
+        assert!(parse("21474836.48").is_err());
+        assert!(parse("7610867035003").is_err());
+    }
+
+    #[test]
+    fn test_parse_divisor() {
+        let currency = Currency {
+            code: "JPY".into(),
+            divisor: 1,
+            format: CurrencyFormat::default(),
+        };
+        assert_eq!(Money::parse("25", &currency).unwrap().minor_units(), 25);
+        assert!(Money::parse("2.5", &currency).is_err());
+    }
+
+    #[test]
+    fn test_exchange_at_rate() {
+        let eur = Currency {
+            code: "EUR".into(),
+            ..chf()
+        };
+        let jpy = Currency {
+            code: "JPY".into(),
+            divisor: 1,
+            format: CurrencyFormat::default(),
+        };
+        let exchange = Exchange::at_rate(Money(1000), &eur, &chf(), "0.95").unwrap();
+        assert_eq!((exchange.amount, exchange.currency.as_str()), (950, "CHF"));
+        assert_eq!(
+            Exchange::at_rate(Money(333), &chf(), &jpy, "165.5")
+                .unwrap()
+                .amount,
+            551
+        );
+        assert_eq!(
+            Exchange::at_rate(Money(-1), &jpy, &chf(), "0.005")
+                .unwrap()
+                .amount,
+            -1
+        );
+        assert!(Exchange::at_rate(Money(1), &chf(), &eur, "0").is_err());
+        assert!(Exchange::at_rate(Money(1), &chf(), &eur, "-1").is_err());
+        assert!(Exchange::at_rate(Money(1), &chf(), &eur, "abc").is_err());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        let currency = Currency {
+            code: "EUR".into(),
+            divisor: 100,
+            format: CurrencyFormat {
+                symbol: Some("€".into()),
+                decimal_separator: ",".into(),
+                ..Default::default()
+            },
+        };
+        assert_eq!(Money::parse("2,50€", &currency).unwrap().minor_units(), 250);
+        assert_eq!(Money::parse("€ 2.5", &currency).unwrap().minor_units(), 250);
+        assert_eq!(Money::parse("2 EUR", &currency).unwrap().minor_units(), 200);
+    }
+}
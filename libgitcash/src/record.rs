@@ -0,0 +1,110 @@
+use chrono::{DateTime, FixedOffset, TimeZone};
+use git2::{Commit, Oid};
+use serde::{Deserialize, Serialize};
+
+use crate::transaction::Transaction;
+
+/// A transaction together with the commit it was loaded from
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TransactionRecord {
+    /// ID of the commit containing the transaction
+    #[serde(with = "serde_oid")]
+    pub id: Oid,
+    /// Author of the commit
+    pub author: CommitSignature,
+    /// Committer of the commit
+    pub committer: CommitSignature,
+    /// Commit time (in the committer's timezone)
+    pub time: DateTime<FixedOffset>,
+    /// The description in the first line of the commit message (without prefix)
+    pub title: String,
+    /// The parsed transaction
+    pub transaction: Transaction,
+    /// ID of the commit that reverted this transaction, if any
+    #[serde(with = "serde_oid::option")]
+    pub reverted_by: Option<Oid>,
+}
+
+impl TransactionRecord {
+    pub(crate) fn new(commit: &Commit, title: String, transaction: Transaction) -> Self {
+        Self {
+            id: commit.id(),
+            author: CommitSignature::from(&commit.author()),
+            committer: CommitSignature::from(&commit.committer()),
+            time: convert_time(commit.time()),
+            title,
+            transaction,
+            reverted_by: None,
+        }
+    }
+
+    /// Return whether this transaction was reverted
+    pub fn is_reverted(&self) -> bool {
+        self.reverted_by.is_some()
+    }
+}
+
+/// Author or committer of a commit
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub struct CommitSignature {
+    pub name: String,
+    pub email: String,
+    pub time: DateTime<FixedOffset>,
+}
+
+impl From<&git2::Signature<'_>> for CommitSignature {
+    fn from(signature: &git2::Signature) -> Self {
+        Self {
+            name: String::from_utf8_lossy(signature.name_bytes()).into_owned(),
+            email: String::from_utf8_lossy(signature.email_bytes()).into_owned(),
+            time: convert_time(signature.when()),
+        }
+    }
+}
+
+/// Convert a libgit2 timestamp into a date with timezone offset
+pub(crate) fn convert_time(time: git2::Time) -> DateTime<FixedOffset> {
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    offset
+        .timestamp_opt(time.seconds(), 0)
+        .single()
+        .unwrap_or_default()
+}
+
+/// (De)serialize commit IDs as hex strings
+pub(crate) mod serde_oid {
+    use git2::Oid;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(oid: &Oid, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(oid)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Oid, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Oid::from_str(&hex).map_err(D::Error::custom)
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            oid: &Option<Oid>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match oid {
+                Some(oid) => serializer.collect_str(oid),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Oid>, D::Error> {
+            Option::<String>::deserialize(deserializer)?
+                .map(|hex| Oid::from_str(&hex).map_err(D::Error::custom))
+                .transpose()
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::HashSet, io, path::Path, str::FromStr};
+
+use chrono::NaiveDate;
+use git2::{Commit, Repository};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::read_committed_file, error::Error, transaction::Account, Repo};
+
+/// File name of the account registry
+pub(crate) const REGISTRY_FILE_NAME: &str = "accounts.toml";
+
+/// Metadata of an account
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+pub struct AccountInfo {
+    /// The account, e.g. "user:danilo"
+    pub account: Account,
+    /// Human-readable name (any characters are allowed, unlike in account
+    /// names)
+    pub display_name: Option<String>,
+    /// Contact information, e.g. an e-mail address
+    pub contact: Option<String>,
+    /// Date the account was created, e.g. "2023-08-15"
+    pub created: Option<NaiveDate>,
+    /// Archived accounts (e.g. of members who left) are hidden by clients
+    #[serde(default)]
+    pub archived: bool,
+}
+
+impl AccountInfo {
+    /// Create an empty entry for an account
+    pub fn new(account: Account) -> Self {
+        Self {
+            account,
+            display_name: None,
+            contact: None,
+            created: None,
+            archived: false,
+        }
+    }
+}
+
+/// The account registry (`accounts.toml`) of a repository
+///
+/// Registering accounts is optional, accounts still come into existence by
+/// usage.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Registry {
+    #[serde(default, rename = "account")]
+    pub accounts: Vec<AccountInfo>,
+}
+
+impl Registry {
+    /// Load the registry in the specified repo path (empty if there is none)
+    pub fn load(repo_path: &Path) -> Result<Self, Error> {
+        match std::fs::read_to_string(repo_path.join(REGISTRY_FILE_NAME)) {
+            Ok(registry_string) => Self::from_str(&registry_string),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Error::RepoError(format!(
+                "Could not read {}: {}",
+                REGISTRY_FILE_NAME, e
+            ))),
+        }
+    }
+
+    /// Load the registry from a commit (empty if there is none)
+    pub fn from_commit(repository: &Repository, commit: &Commit) -> Result<Self, Error> {
+        match read_committed_file(repository, commit, REGISTRY_FILE_NAME)? {
+            Some(registry_string) => Self::from_str(&registry_string),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Look up the metadata of an account
+    pub fn lookup(&self, account: &Account) -> Option<&AccountInfo> {
+        self.accounts.iter().find(|info| &info.account == account)
+    }
+
+    /// Add the metadata of an account, or replace it if the account is
+    /// already registered
+    pub fn set(&mut self, info: AccountInfo) {
+        match self.accounts.iter_mut().find(|i| i.account == info.account) {
+            Some(existing) => *existing = info,
+            None => self.accounts.push(info),
+        }
+    }
+}
+
+impl FromStr for Registry {
+    type Err = Error;
+
+    fn from_str(registry_string: &str) -> Result<Self, Error> {
+        let registry: Registry = toml::from_str(registry_string).map_err(|e| {
+            Error::RepoError(format!("Could not parse {}: {}", REGISTRY_FILE_NAME, e))
+        })?;
+        let mut accounts = HashSet::new();
+        for info in &registry.accounts {
+            if !accounts.insert(&info.account) {
+                return Err(Error::RepoError(format!(
+                    "Account {} is registered more than once",
+                    info.account
+                )));
+            }
+        }
+        Ok(registry)
+    }
+}
+
+impl Repo {
+    /// Return the account registry
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Return the metadata of an account, if it is registered
+    pub fn account_info(&self, account: &Account) -> Option<&AccountInfo> {
+        self.registry.lookup(account)
+    }
+
+    /// Add or update the metadata of an account and commit the updated
+    /// `accounts.toml`
+    pub fn set_account_info(&mut self, info: AccountInfo) -> Result<(), Error> {
+        let workdir = self
+            .repository
+            .workdir()
+            .ok_or_else(|| {
+                Error::RepoError("Cannot register accounts in a bare repository".into())
+            })?
+            .to_path_buf();
+        let message = match self.registry.lookup(&info.account) {
+            Some(_) => format!("Update account {}", info.account),
+            None => format!("Register account {}", info.account),
+        };
+        self.registry.set(info);
+
+        // Write and commit registry
+        let registry_string = toml::to_string(&self.registry)
+            .map_err(|e| Error::RepoError(format!("Could not serialize registry: {}", e)))?;
+        std::fs::write(workdir.join(REGISTRY_FILE_NAME), registry_string).map_err(|e| {
+            Error::RepoError(format!("Could not write {}: {}", REGISTRY_FILE_NAME, e))
+        })?;
+        let mut index = self.repository.index()?;
+        index.add_path(Path::new(REGISTRY_FILE_NAME))?;
+        index.write()?;
+        self.commit(&message)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestRepo;
+
+    #[test]
+    fn test_from_str() {
+        let registry = Registry::from_str(
+            "[[account]]\naccount = \"user:danilo\"\ndisplay_name = \"Danilo Bargén\"\n\
+             created = \"2023-08-15\"\narchived = true",
+        )
+        .unwrap();
+        let info = registry.lookup(&Account::user("danilo").unwrap()).unwrap();
+        assert_eq!(info.display_name.as_deref(), Some("Danilo Bargén"));
+        assert_eq!(info.created, NaiveDate::from_ymd_opt(2023, 8, 15));
+        assert!(info.archived);
+
+        let duplicate = "[[account]]\naccount = \"user:a\"\n[[account]]\naccount = \"user:a\"";
+        assert!(Registry::from_str(duplicate).is_err());
+    }
+
+    #[test]
+    fn test_set_account_info() {
+        let test_repo = TestRepo::new();
+        let mut repo = Repo::open(test_repo.path()).unwrap();
+        let account = Account::user("danilo").unwrap();
+        let mut info = AccountInfo::new(account.clone());
+        info.contact = Some("danilo@example.com".into());
+        repo.set_account_info(info.clone()).unwrap();
+        info.archived = true;
+        repo.set_account_info(info.clone()).unwrap();
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        assert_eq!(repo.account_info(&account), Some(&info));
+        assert!(repo.accounts().contains(&account));
+        let head = test_repo.repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("Update account user:danilo"));
+    }
+}
@@ -0,0 +1,98 @@
+use git2::Oid;
+
+use crate::{
+    error::Error,
+    record::TransactionRecord,
+    transaction::{extract_revert_reference, extract_title, extract_transaction, REVERT_PREFIX},
+};
+
+/// Minimum length of an abbreviated commit ID in a revert reference
+const MIN_REFERENCE_LENGTH: usize = 7;
+
+/// Find the transaction that is cancelled by a revert commit
+///
+/// If the commit message contains a "This reverts commit <id>." line, the
+/// transaction is looked up by its commit ID. Otherwise, the most recent
+/// transaction with the same transaction data is used. The title is not
+/// compared, as it depends on the configured currency formatting.
+///
+/// Returns the index of the reverted transaction in `records`.
+pub(crate) fn find_reverted_transaction(
+    records: &[TransactionRecord],
+    revert_id: Oid,
+    message: &str,
+) -> Result<usize, Error> {
+    let index = match extract_revert_reference(message) {
+        Some(reference) => find_by_reference(records, revert_id, reference)?,
+        None => find_by_message(records, revert_id, message)?,
+    };
+    if let Some(reverted_by) = records[index].reverted_by {
+        return Err(Error::RevertError(format!(
+            "Revert {} targets transaction {}, which was already reverted by {}",
+            revert_id, records[index].id, reverted_by
+        )));
+    }
+    Ok(index)
+}
+
+/// Find a transaction by (possibly abbreviated) commit ID
+fn find_by_reference(
+    records: &[TransactionRecord],
+    revert_id: Oid,
+    reference: &str,
+) -> Result<usize, Error> {
+    if reference.len() < MIN_REFERENCE_LENGTH {
+        return Err(Error::RevertError(format!(
+            "Revert {} references commit {}, but at least {} characters are required",
+            revert_id, reference, MIN_REFERENCE_LENGTH
+        )));
+    }
+    let reference = reference.to_ascii_lowercase();
+    let mut matches = records
+        .iter()
+        .enumerate()
+        .filter(|(_, record)| record.id.to_string().starts_with(&reference));
+    match (matches.next(), matches.next()) {
+        (Some((index, _)), None) => Ok(index),
+        (Some(_), Some(_)) => Err(Error::RevertError(format!(
+            "Revert {} references commit {}, which is ambiguous",
+            revert_id, reference
+        ))),
+        (None, _) => Err(Error::RevertError(format!(
+            "Revert {} references commit {}, which is not a known transaction",
+            revert_id, reference
+        ))),
+    }
+}
+
+/// Find a transaction by comparing the transaction data
+fn find_by_message(
+    records: &[TransactionRecord],
+    revert_id: Oid,
+    message: &str,
+) -> Result<usize, Error> {
+    let title = extract_title(message, REVERT_PREFIX);
+    let transaction = extract_transaction(message)?;
+    let candidates = records
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, record)| record.transaction == transaction)
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    // Prefer the most recent transaction that was not reverted yet. If all
+    // candidates were reverted already, return the most recent one, so that
+    // the caller can report it.
+    candidates
+        .iter()
+        .copied()
+        .find(|&index| !records[index].is_reverted())
+        .or_else(|| candidates.first().copied())
+        .ok_or_else(|| {
+            Error::RevertError(format!(
+                "Revert {} does not match any transaction: {}",
+                revert_id, title
+            ))
+        })
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::{
+    record::TransactionRecord,
+    transaction::{AccountType, Transaction},
+    Repo,
+};
+
+/// Length of the periods that transactions are grouped by
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Period {
+    Day,
+    /// ISO week, starting on Monday
+    Week,
+    Month,
+}
+
+impl Period {
+    /// Return the first day of the period containing `date`
+    pub fn start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - Duration::days(date.weekday().num_days_from_monday().into()),
+            Period::Month => date.with_day(1).expect("Every month has a first day"),
+        }
+    }
+
+    /// Return a label for the period starting at `start`, e.g. "2023-08-15",
+    /// "2023-W33" or "2023-08"
+    pub fn label(&self, start: NaiveDate) -> String {
+        match self {
+            Period::Day => start.format("%Y-%m-%d").to_string(),
+            Period::Week => {
+                let week = start.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Period::Month => start.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+/// What transactions are aggregated by
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Aggregation {
+    /// Revenue per point of sale account
+    Revenue,
+    /// Sales per product class (see [`TransactionMeta`](crate::TransactionMeta))
+    Class,
+    /// Sales per EAN
+    Ean,
+    /// Deposits per source account
+    Deposits,
+    /// Purchases per user account
+    Consumers,
+}
+
+impl Aggregation {
+    /// Return the key a transaction is aggregated under, or `None` if it
+    /// isn't relevant for this aggregation
+    fn key(&self, transaction: &Transaction) -> Option<String> {
+        let is_sale = transaction.to.account_type == AccountType::PointOfSale;
+        let meta = transaction.meta.as_ref();
+        match self {
+            Aggregation::Revenue if is_sale => Some(transaction.to.to_string()),
+            Aggregation::Class if is_sale => Some(
+                meta.and_then(|meta| meta.class.clone())
+                    .unwrap_or_else(|| "unclassified".into()),
+            ),
+            Aggregation::Ean if is_sale => meta?.ean.map(|ean| ean.to_string()),
+            Aggregation::Deposits if transaction.from.account_type == AccountType::Source => {
+                Some(transaction.from.to_string())
+            }
+            Aggregation::Consumers
+                if is_sale && transaction.from.account_type == AccountType::User =>
+            {
+                Some(transaction.from.to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A query for [`Repo::aggregate`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AggregationQuery {
+    pub aggregation: Aggregation,
+    pub period: Period,
+    /// First day to include (commit date, inclusive)
+    pub since: Option<NaiveDate>,
+    /// Last day to include (commit date, inclusive)
+    pub until: Option<NaiveDate>,
+}
+
+/// Sum of the transactions with the same key in a period
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AggregateRow {
+    /// First day of the period
+    pub period_start: NaiveDate,
+    /// The account, class or EAN
+    pub key: String,
+    /// Currency code of the amount
+    pub currency: String,
+    /// Number of transactions
+    pub count: usize,
+    /// Sum of the amounts
+    pub amount: i64,
+}
+
+impl Repo {
+    /// Aggregate the non-reverted transactions in the time range of the query
+    /// by period and key
+    ///
+    /// Transactions are assigned to periods by the date of their commit (in
+    /// the committer's timezone). Exchanges are not included. Rows are sorted
+    /// by period, then by amount (highest first) and key.
+    pub fn aggregate(&self, query: &AggregationQuery) -> Vec<AggregateRow> {
+        let in_range = |record: &&TransactionRecord| {
+            let date = record.time.date_naive();
+            query.since.map_or(true, |since| date >= since)
+                && query.until.map_or(true, |until| date <= until)
+        };
+
+        let mut sums: HashMap<(NaiveDate, String, &str), (usize, i64)> = HashMap::new();
+        for record in self.transactions.iter().filter(in_range) {
+            let transaction = &record.transaction;
+            if record.is_reverted() || transaction.exchange.is_some() {
+                continue;
+            }
+            let Some(key) = query.aggregation.key(transaction) else {
+                continue;
+            };
+            let period_start = query.period.start(record.time.date_naive());
+            let currency = transaction
+                .currency
+                .as_deref()
+                .unwrap_or(&self.config.currency.code);
+            let (count, amount) = sums.entry((period_start, key, currency)).or_default();
+            *count += 1;
+            *amount += i64::from(transaction.amount);
+        }
+
+        let mut rows = sums
+            .into_iter()
+            .map(
+                |((period_start, key, currency), (count, amount))| AggregateRow {
+                    period_start,
+                    key,
+                    currency: currency.to_string(),
+                    count,
+                    amount,
+                },
+            )
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| {
+            (a.period_start, -a.amount, &a.key, &a.currency).cmp(&(
+                b.period_start,
+                -b.amount,
+                &b.key,
+                &b.currency,
+            ))
+        });
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestRepo;
+
+    #[test]
+    fn test_period_start() {
+        let date = NaiveDate::from_ymd_opt(2023, 8, 17).unwrap();
+        assert_eq!(Period::Day.start(date), date);
+        assert_eq!(
+            Period::Week.start(date),
+            NaiveDate::from_ymd_opt(2023, 8, 14).unwrap()
+        );
+        assert_eq!(
+            Period::Month.start(date),
+            NaiveDate::from_ymd_opt(2023, 8, 1).unwrap()
+        );
+        assert_eq!(Period::Week.label(Period::Week.start(date)), "2023-W33");
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let test_repo = TestRepo::new();
+        let purchase = |user: &str, amount: i32, class: &str| {
+            format!(
+                "from = \"user:{}\"\nto = \"pos:fridge\"\namount = {}\n\n[meta]\nclass = \"{}\"",
+                user, amount, class
+            )
+        };
+        // 2023-07-31 and 2023-08-01 (UTC)
+        test_repo.commit_transaction_at("A", &purchase("a", 250, "softdrink"), 1690800000);
+        test_repo.commit_transaction_at("B", &purchase("b", 300, "beer"), 1690880000);
+        test_repo.commit_transaction_at("C", &purchase("a", 250, "softdrink"), 1690890000);
+
+        let repo = Repo::open(test_repo.path()).unwrap();
+        let mut query = AggregationQuery {
+            aggregation: Aggregation::Consumers,
+            period: Period::Month,
+            since: None,
+            until: None,
+        };
+        let rows = repo
+            .aggregate(&query)
+            .into_iter()
+            .map(|row| {
+                (
+                    Period::Month.label(row.period_start),
+                    row.key,
+                    row.count,
+                    row.amount,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                ("2023-07".to_string(), "user:a".to_string(), 1, 250),
+                ("2023-08".to_string(), "user:b".to_string(), 1, 300),
+                ("2023-08".to_string(), "user:a".to_string(), 1, 250),
+            ]
+        );
+
+        query.aggregation = Aggregation::Class;
+        query.since = NaiveDate::from_ymd_opt(2023, 8, 1);
+        let rows = repo.aggregate(&query);
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].key.as_str(), rows[0].amount), ("beer", 300));
+        assert_eq!((rows[1].key.as_str(), rows[1].amount), ("softdrink", 250));
+    }
+}
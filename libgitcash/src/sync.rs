@@ -0,0 +1,290 @@
+use std::cell::RefCell;
+
+use git2::{
+    build::CheckoutBuilder, Commit, Cred, CredentialType, ErrorCode, FetchOptions, Oid,
+    PushOptions, RemoteCallbacks,
+};
+use tracing::{debug, info};
+
+use crate::{error::Error, signature, Repo};
+
+/// Number of fetch/merge/push cycles before giving up
+///
+/// If another node pushes between our fetch and our push, the push is
+/// rejected as non-fast-forward and we have to try again.
+const MAX_SYNC_ATTEMPTS: u32 = 5;
+
+/// How the remote history was integrated into the local history
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Integration {
+    /// The remote had no new commits
+    UpToDate,
+    /// The local history had no new commits and was fast-forwarded
+    FastForward,
+    /// Both histories diverged and were joined with a merge commit
+    Merge,
+}
+
+/// Summary of a sync with a remote
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SyncResult {
+    /// How the remote history was integrated (in the last attempt)
+    pub integration: Integration,
+    /// Whether local commits were pushed to the remote
+    pub pushed: bool,
+    /// Number of fetch/merge/push cycles that were needed
+    pub attempts: u32,
+    /// Number of transactions that were received from the remote
+    pub new_transactions: usize,
+}
+
+/// Outcome of a single push
+enum PushOutcome {
+    Pushed,
+    /// The remote has new commits, we need to fetch and merge again
+    NotFastForward,
+}
+
+/// Callbacks for authenticating against SSH or HTTP remotes
+fn remote_callbacks<'a>() -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|_url, username, allowed| {
+        if allowed.contains(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else {
+            Cred::default()
+        }
+    });
+    callbacks
+}
+
+impl Repo {
+    /// Synchronize the current branch with a remote
+    ///
+    /// New commits are fetched from the remote. If both histories diverged,
+    /// the remote branch is merged into the local branch (local commits are
+    /// never rewritten, so references in reverts stay valid). Afterwards the
+    /// result is pushed and all transactions are reloaded.
+    ///
+    /// If the push is rejected because the remote changed in the meantime, the
+    /// whole cycle is retried a few times.
+    pub fn sync(&mut self, remote_name: &str) -> Result<SyncResult, Error> {
+        let transactions_before = self.transactions.len();
+        let branch = self.current_branch()?;
+        let mut attempts = 0;
+        let (integration, pushed) = loop {
+            attempts += 1;
+            debug!(
+                "Syncing branch {} with {} (attempt {})",
+                branch, remote_name, attempts
+            );
+            let remote_tip = self.fetch(remote_name, &branch)?;
+            let integration = match remote_tip {
+                Some(remote_tip) => self.integrate(remote_name, remote_tip)?,
+                None => Integration::UpToDate,
+            };
+            let local_tip = self.repository.head()?.peel_to_commit()?.id();
+            if remote_tip == Some(local_tip) {
+                break (integration, false);
+            }
+            match self.push(remote_name, &branch)? {
+                PushOutcome::Pushed => break (integration, true),
+                PushOutcome::NotFastForward if attempts < MAX_SYNC_ATTEMPTS => {
+                    info!("Remote {} changed during sync, retrying", remote_name);
+                }
+                PushOutcome::NotFastForward => {
+                    return Err(Error::SyncError(format!(
+                        "Push to {} was rejected as non-fast-forward {} times, giving up",
+                        remote_name, attempts
+                    )))
+                }
+            }
+        };
+
+        self.reload()?;
+        Ok(SyncResult {
+            integration,
+            pushed,
+            attempts,
+            new_transactions: self.transactions.len().saturating_sub(transactions_before),
+        })
+    }
+
+    /// Return the short name of the checked out branch
+    fn current_branch(&self) -> Result<String, Error> {
+        let head = self.repository.head()?;
+        if !head.is_branch() {
+            return Err(Error::SyncError(
+                "HEAD is detached, cannot determine branch to sync".into(),
+            ));
+        }
+        head.shorthand()
+            .map(ToString::to_string)
+            .ok_or_else(|| Error::SyncError("Branch name is not valid UTF-8".into()))
+    }
+
+    /// Fetch a branch from the remote, return the remote tip (if it exists)
+    fn fetch(&self, remote_name: &str, branch: &str) -> Result<Option<Oid>, Error> {
+        let tracking_ref = format!("refs/remotes/{}/{}", remote_name, branch);
+        let refspec = format!("+refs/heads/{}:{}", branch, tracking_ref);
+        let mut remote = self.repository.find_remote(remote_name)?;
+        let mut options = FetchOptions::new();
+        options.remote_callbacks(remote_callbacks());
+        remote.fetch(&[&refspec], Some(&mut options), None)?;
+        match self.repository.refname_to_id(&tracking_ref) {
+            Ok(oid) => Ok(Some(oid)),
+            Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Integrate the remote tip into the current branch
+    fn integrate(&self, remote_name: &str, remote_tip: Oid) -> Result<Integration, Error> {
+        let local = self.repository.head()?.peel_to_commit()?;
+        let remote = self.repository.find_commit(remote_tip)?;
+        if local.id() == remote.id()
+            || self
+                .repository
+                .graph_descendant_of(local.id(), remote.id())?
+        {
+            return Ok(Integration::UpToDate);
+        }
+
+        if self
+            .repository
+            .graph_descendant_of(remote.id(), local.id())?
+        {
+            debug!("Fast-forwarding to {}", remote.id());
+            self.move_head(&remote, "gitcash: fast-forward")?;
+            return Ok(Integration::FastForward);
+        }
+
+        debug!("Merging {} into {}", remote.id(), local.id());
+        let mut index = self.repository.merge_commits(&local, &remote, None)?;
+        if index.has_conflicts() {
+            return Err(Error::SyncError(format!(
+                "Merging {} into {} results in conflicts, please resolve them manually",
+                remote.id(),
+                local.id()
+            )));
+        }
+        let tree = self
+            .repository
+            .find_tree(index.write_tree_to(&self.repository)?)?;
+        let sig = signature()?;
+        let message = format!("Merge remote-tracking branch '{}'", remote_name);
+        let merge_commit =
+            self.repository
+                .commit(None, &sig, &sig, &message, &tree, &[&local, &remote])?;
+        self.move_head(&self.repository.find_commit(merge_commit)?, &message)?;
+        Ok(Integration::Merge)
+    }
+
+    /// Point the current branch to the specified commit and update the
+    /// working directory (if any)
+    fn move_head(&self, commit: &Commit, reflog_message: &str) -> Result<(), Error> {
+        if !self.repository.is_bare() {
+            self.repository
+                .checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+        }
+        self.repository
+            .head()?
+            .set_target(commit.id(), reflog_message)?;
+        Ok(())
+    }
+
+    /// Push the current branch to the remote
+    fn push(&self, remote_name: &str, branch: &str) -> Result<PushOutcome, Error> {
+        let refspec = format!("refs/heads/{}:refs/heads/{}", branch, branch);
+        let rejection = RefCell::new(None);
+        let mut callbacks = remote_callbacks();
+        callbacks.push_update_reference(|_refname, status| {
+            *rejection.borrow_mut() = status.map(ToString::to_string);
+            Ok(())
+        });
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+
+        let mut remote = self.repository.find_remote(remote_name)?;
+        match remote.push(&[&refspec], Some(&mut options)) {
+            Ok(()) => {}
+            Err(e) if e.code() == ErrorCode::NotFastForward => {
+                return Ok(PushOutcome::NotFastForward)
+            }
+            Err(e) => return Err(e.into()),
+        }
+        drop(options);
+
+        match rejection.into_inner() {
+            None => Ok(PushOutcome::Pushed),
+            Some(status)
+                if status.contains("non-fast-forward") || status.contains("fetch first") =>
+            {
+                Ok(PushOutcome::NotFastForward)
+            }
+            Some(status) => Err(Error::SyncError(format!(
+                "Push to {} was rejected: {}",
+                remote_name, status
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Repository;
+    use tempfile::TempDir;
+
+    use crate::{test_utils::TestRepo, Account, Integration, Repo, Transaction};
+
+    fn purchase(name: &str) -> Transaction {
+        Transaction {
+            from: Account::user(name).unwrap(),
+            to: Account::point_of_sale("fridge").unwrap(),
+            amount: 250,
+            currency: None,
+            description: None,
+            meta: None,
+            exchange: None,
+        }
+    }
+
+    #[test]
+    fn test_sync_diverged() {
+        // Create remote and two nodes
+        let remote_dir = TempDir::new().unwrap();
+        Repository::init_bare(remote_dir.path()).unwrap();
+        let url = format!("file://{}", remote_dir.path().display());
+        let first = TestRepo::new();
+        first.repo.remote("origin", &url).unwrap();
+        let mut first_repo = Repo::open(first.path()).unwrap();
+        let result = first_repo.sync("origin").unwrap();
+        assert!(result.pushed);
+        let second_dir = TempDir::new().unwrap();
+        Repository::clone(&url, second_dir.path()).unwrap();
+        let mut second_repo = Repo::open(second_dir.path()).unwrap();
+
+        // Create diverging transactions
+        first_repo.create_transaction(purchase("a")).unwrap();
+        second_repo.create_transaction(purchase("b")).unwrap();
+
+        let result = first_repo.sync("origin").unwrap();
+        assert_eq!(result.integration, Integration::UpToDate);
+        assert!(result.pushed);
+
+        let result = second_repo.sync("origin").unwrap();
+        assert_eq!(result.integration, Integration::Merge);
+        assert!(result.pushed);
+        assert_eq!(result.new_transactions, 1);
+        assert_eq!(second_repo.transactions().len(), 2);
+
+        let result = first_repo.sync("origin").unwrap();
+        assert_eq!(result.integration, Integration::FastForward);
+        assert!(!result.pushed);
+        assert_eq!(first_repo.transactions().len(), 2);
+        assert_eq!(
+            first_repo.balances().unwrap(),
+            second_repo.balances().unwrap()
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use git2::{Oid, Repository, Signature, Time};
+use tempfile::TempDir;
+
+/// Default repository config used in tests
+pub const CONFIG: &str = r#"name = "Test"
+
+[currency]
+code = "CHF"
+divisor = 100
+"#;
+
+/// A temporary GitCash repository
+pub struct TestRepo {
+    pub dir: TempDir,
+    pub repo: Repository,
+}
+
+impl TestRepo {
+    /// Create a new repository containing a committed `gitcash.toml`
+    pub fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        std::fs::write(dir.path().join("gitcash.toml"), CONFIG).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("gitcash.toml")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = Self::signature();
+        repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[])
+            .unwrap();
+        drop(tree);
+        Self { dir, repo }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Create an empty commit with the specified message on top of HEAD
+    pub fn commit(&self, message: &str) -> Oid {
+        self.commit_with_signature(message, Self::signature())
+    }
+
+    /// Create a transaction commit at the specified time (seconds since the
+    /// epoch, UTC)
+    pub fn commit_transaction_at(&self, title: &str, data: &str, time: i64) -> Oid {
+        let sig = Signature::new("Test", "test@example.com", &Time::new(time, 0)).unwrap();
+        let message = format!("Transaction: {}\n\n---\n{}\n---", title, data);
+        self.commit_with_signature(&message, sig)
+    }
+
+    fn commit_with_signature(&self, message: &str, sig: Signature) -> Oid {
+        let head = self.repo.head().unwrap().peel_to_commit().unwrap();
+        self.repo
+            .commit(
+                Some("HEAD"),
+                &sig,
+                &sig,
+                message,
+                &head.tree().unwrap(),
+                &[&head],
+            )
+            .unwrap()
+    }
+
+    /// Create a transaction commit
+    pub fn commit_transaction(&self, title: &str, data: &str) -> Oid {
+        self.commit(&format!("Transaction: {}\n\n---\n{}\n---", title, data))
+    }
+
+    fn signature() -> Signature<'static> {
+        Signature::now("Test", "test@example.com").unwrap()
+    }
+}
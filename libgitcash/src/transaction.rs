@@ -1,13 +1,33 @@
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
 use crate::{error::Error, RepoConfig};
-use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Commit message prefix of a transaction
+pub(crate) const TRANSACTION_PREFIX: &str = "Transaction: ";
+
+/// Commit message prefix of a revert
+pub(crate) const REVERT_PREFIX: &str = "Revert: ";
+
+/// Commit message prefix of a restock
+pub(crate) const RESTOCK_PREFIX: &str = "Restock: ";
+
+/// Line prefix used to reference the reverted commit (same as `git revert`)
+pub(crate) const REVERT_REFERENCE_PREFIX: &str = "This reverts commit ";
+
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Transaction {
     pub from: Account,
     pub to: Account,
     pub amount: i32,
+    /// Code of the currency of the amount (the primary currency if not set)
+    pub currency: Option<String>,
     pub description: Option<String>,
     pub meta: Option<TransactionMeta>,
+    /// Makes this an exchange, in which `to` pays back an amount in another
+    /// currency to `from`
+    pub exchange: Option<Exchange>,
 }
 
 impl Transaction {
@@ -16,22 +36,83 @@ impl Transaction {
             return format!("Transaction: Add user {}", self.to.name);
         }
 
+        let currency = config
+            .find_currency(self.currency.as_deref())
+            .unwrap_or(&config.currency);
+        if let Some(exchange) = &self.exchange {
+            let target_currency = config
+                .find_currency(Some(&exchange.currency))
+                .unwrap_or(&config.currency);
+            return format!(
+                "Transaction: {:?} {} exchanges {} for {} with {:?} {}",
+                self.from.account_type,
+                self.from.name,
+                currency.format_amount(self.amount.into()),
+                target_currency.format_amount(exchange.amount.into()),
+                self.to.account_type,
+                self.to.name,
+            );
+        }
+
         format!(
-            "Transaction: {:?} {} pays {:.2} {} to {:?} {}",
+            "Transaction: {:?} {} pays {} to {:?} {}",
             self.from.account_type,
             self.from.name,
-            self.amount as f32 / config.currency.divisor as f32,
-            config.currency.code,
+            currency.format_amount(self.amount.into()),
             self.to.account_type,
             self.to.name,
         )
     }
+
+    /// Return the movements of money caused by this transaction (two for
+    /// exchanges, one otherwise)
+    pub fn postings(&self) -> Vec<Posting<'_>> {
+        let mut postings = vec![Posting {
+            from: &self.from,
+            to: &self.to,
+            amount: self.amount.into(),
+            currency: self.currency.as_deref(),
+        }];
+        if let Some(exchange) = &self.exchange {
+            postings.push(Posting {
+                from: &self.to,
+                to: &self.from,
+                amount: exchange.amount.into(),
+                currency: Some(&exchange.currency),
+            });
+        }
+        postings
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct TransactionMeta {
     pub class: Option<String>,
     pub ean: Option<u64>,
+    /// Hash of the CSV row the transaction was imported from
+    pub import_hash: Option<String>,
+}
+
+/// The second part of an exchange transaction
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Exchange {
+    /// Code of the currency that is paid back
+    pub currency: String,
+    /// The amount that is paid back (as integer, see currency divisor)
+    pub amount: i32,
+    /// The exchange rate, as units of this currency per unit of the
+    /// transaction currency (e.g. "0.95")
+    pub rate: String,
+}
+
+/// Money moved from one account to another in a single currency
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Posting<'a> {
+    pub from: &'a Account,
+    pub to: &'a Account,
+    pub amount: i64,
+    /// Currency code (the primary currency if not set)
+    pub currency: Option<&'a str>,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Hash, Clone, Copy)]
@@ -44,6 +125,27 @@ pub enum AccountType {
     Source,
 }
 
+impl AccountType {
+    /// Return the prefix used in account identifiers (e.g. "pos")
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            AccountType::User => "user",
+            AccountType::PointOfSale => "pos",
+            AccountType::Source => "source",
+        }
+    }
+
+    /// Return whether accounts of this type may send money
+    pub fn can_send(&self) -> bool {
+        *self != AccountType::PointOfSale
+    }
+
+    /// Return whether accounts of this type may receive money
+    pub fn can_receive(&self) -> bool {
+        *self != AccountType::Source
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Hash, Clone, Deserialize, Serialize)]
 #[serde(try_from = "String", into = "String")]
 /// An account can hold money
@@ -89,17 +191,15 @@ impl Account {
     }
 }
 
-impl Into<String> for Account {
-    fn into(self) -> String {
-        format!(
-            "{}:{}",
-            match self.account_type {
-                AccountType::User => "user",
-                AccountType::PointOfSale => "pos",
-                AccountType::Source => "source",
-            },
-            self.name,
-        )
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.account_type.prefix(), self.name)
+    }
+}
+
+impl From<Account> for String {
+    fn from(account: Account) -> String {
+        account.to_string()
     }
 }
 
@@ -139,8 +239,50 @@ impl TryFrom<String> for Account {
     }
 }
 
+/// Return the first line of a commit message without the specified prefix
+pub(crate) fn extract_title<'a>(commit_message: &'a str, prefix: &str) -> &'a str {
+    let first_line = commit_message.lines().next().unwrap_or_default();
+    first_line.strip_prefix(prefix).unwrap_or(first_line).trim()
+}
+
+/// Extract the (possibly abbreviated) commit ID referenced by a
+/// "This reverts commit <id>." line in a commit message
+pub(crate) fn extract_revert_reference(commit_message: &str) -> Option<&str> {
+    commit_message.lines().find_map(|line| {
+        let reference = line
+            .trim()
+            .strip_prefix(REVERT_REFERENCE_PREFIX)?
+            .trim_end_matches('.');
+        if reference.is_empty() || !reference.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(reference)
+    })
+}
+
 /// Extract a transaction from a commit message
 pub(crate) fn extract_transaction(commit_message: &str) -> Result<Transaction, Error> {
+    toml::from_str(&extract_data(commit_message))
+        .map_err(|e| Error::TransactionParseError(format!("Invalid TOML transaction data: {}", e)))
+}
+
+/// Return the line of the data in a commit message that cannot be parsed as
+/// `T` (if the error can be attributed to a single line)
+pub(crate) fn find_offending_line<T: DeserializeOwned>(commit_message: &str) -> Option<String> {
+    let data = extract_data(commit_message);
+    let span = toml::from_str::<T>(&data).err()?.span()?;
+    if data.get(span.clone())?.contains('\n') {
+        return None;
+    }
+    let start = data[..span.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = data[span.start..]
+        .find('\n')
+        .map_or(data.len(), |i| span.start + i);
+    Some(data[start..end].to_string()).filter(|line| !line.trim().is_empty())
+}
+
+/// Extract the data section between the two `---` markers
+pub(crate) fn extract_data(commit_message: &str) -> String {
     let mut lines = Vec::new();
     let mut in_transaction = false;
     for line in commit_message.lines() {
@@ -148,9 +290,8 @@ pub(crate) fn extract_transaction(commit_message: &str) -> Result<Transaction, E
             false if line == "---" => in_transaction = true,
             true if line == "---" => break,
             false => continue,
-            true => lines.push(line.to_string()),
+            true => lines.push(line),
         }
     }
-    toml::from_str(&lines.join("\n"))
-        .map_err(|e| Error::TransactionParseError(format!("Invalid TOML transaction data: {}", e)))
+    lines.join("\n")
 }
@@ -0,0 +1,278 @@
+use std::fmt;
+
+use git2::Oid;
+
+use crate::{
+    catalog::validate_ean,
+    config::RepoConfig,
+    error::Error,
+    record::TransactionRecord,
+    transaction::{Account, AccountType, Transaction},
+    Repo, TransactionOptions,
+};
+
+/// A rule from the specification that every transaction must follow
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Rule {
+    /// A point of sale can only receive money
+    PointOfSaleCannotSend,
+    /// A source can only be used to deposit money into the system
+    SourceCannotReceive,
+    /// Every currency must be declared in the repo config
+    UnknownCurrency,
+    /// An exchange must convert between two different currencies
+    ExchangeWithinCurrency,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::PointOfSaleCannotSend => write!(f, "Point of sale accounts cannot send money"),
+            Rule::SourceCannotReceive => write!(f, "Source accounts cannot receive money"),
+            Rule::UnknownCurrency => write!(f, "Currency is not configured in gitcash.toml"),
+            Rule::ExchangeWithinCurrency => {
+                write!(f, "Exchanges must be between different currencies")
+            }
+        }
+    }
+}
+
+/// A transaction in the repository history that breaks a rule
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Violation {
+    /// ID of the commit containing the transaction
+    pub commit: Oid,
+    /// The rule that was broken
+    pub rule: Rule,
+    pub from: Account,
+    pub to: Account,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Commit {} ({} -> {}): {}",
+            self.commit, self.from, self.to, self.rule
+        )
+    }
+}
+
+impl Transaction {
+    /// Return all rules broken by this transaction
+    ///
+    /// A negative amount moves money from the `to` account to the `from`
+    /// account, so the account type rules are checked against the actual
+    /// direction of every posting.
+    pub fn broken_rules(&self, config: &RepoConfig) -> Vec<Rule> {
+        let mut rules = Vec::new();
+        for posting in self.postings() {
+            let (sender, receiver) = if posting.amount < 0 {
+                (posting.to, posting.from)
+            } else {
+                (posting.from, posting.to)
+            };
+            if !sender.account_type.can_send() {
+                rules.push(Rule::PointOfSaleCannotSend);
+            }
+            if !receiver.account_type.can_receive() {
+                rules.push(Rule::SourceCannotReceive);
+            }
+            if config.find_currency(posting.currency).is_none() {
+                rules.push(Rule::UnknownCurrency);
+            }
+        }
+        if let Some(exchange) = &self.exchange {
+            let currency = config.find_currency(self.currency.as_deref());
+            if currency.is_some_and(|currency| currency.code == exchange.currency) {
+                rules.push(Rule::ExchangeWithinCurrency);
+            }
+        }
+        rules.sort_by_key(|rule| *rule as u8);
+        rules.dedup();
+        rules
+    }
+
+    /// Return an error for the first rule broken by this transaction
+    pub(crate) fn check_rules(&self, config: &RepoConfig) -> Result<(), Error> {
+        match self.broken_rules(config).first() {
+            Some(rule) => Err(Error::ValidationError(format!(
+                "Transaction from {} to {} is not allowed: {}",
+                self.from, self.to, rule
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Return all non-reverted transactions that break a rule
+pub fn find_violations(records: &[TransactionRecord], config: &RepoConfig) -> Vec<Violation> {
+    records
+        .iter()
+        .filter(|record| !record.is_reverted())
+        .flat_map(|record| {
+            record
+                .transaction
+                .broken_rules(config)
+                .into_iter()
+                .map(|rule| Violation {
+                    commit: record.id,
+                    rule,
+                    from: record.transaction.from.clone(),
+                    to: record.transaction.to.clone(),
+                })
+        })
+        .collect()
+}
+
+impl Repo {
+    /// Check a new payment, deposit or transfer before it is created
+    ///
+    /// Besides the rules of the specification, clients require a positive
+    /// amount, user accounts that already exist, a valid EAN and enough
+    /// balance above the credit limit (unless overdrafts are allowed).
+    pub fn check_transaction(
+        &self,
+        transaction: &Transaction,
+        options: &TransactionOptions,
+    ) -> Result<(), Error> {
+        let accounts = self.accounts();
+        for account in [&transaction.from, &transaction.to] {
+            if account.account_type == AccountType::User && !accounts.contains(account) {
+                return Err(Error::ValidationError(format!(
+                    "Unknown account: {}",
+                    account
+                )));
+            }
+        }
+        let exchange_amount = transaction.exchange.as_ref().map(|e| e.amount);
+        if transaction.amount <= 0 || exchange_amount.is_some_and(|amount| amount <= 0) {
+            return Err(Error::ValidationError("Amount must be positive".into()));
+        }
+        if let Some(ean) = transaction.meta.as_ref().and_then(|meta| meta.ean) {
+            validate_ean(ean)?;
+        }
+        transaction.check_rules(&self.config)?;
+        if !options.allow_overdraft {
+            self.check_limits(transaction)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        test_utils::TestRepo,
+        transaction::{Exchange, TransactionMeta},
+    };
+
+    fn config() -> RepoConfig {
+        RepoConfig::from_str(
+            "name = \"Test\"\n[currency]\ncode = \"CHF\"\ndivisor = 100\n\
+             [[other_currency]]\ncode = \"EUR\"\ndivisor = 100",
+        )
+        .unwrap()
+    }
+
+    fn transaction(from: &str, to: &str, amount: i32) -> Transaction {
+        Transaction {
+            from: Account::try_from(from.to_string()).unwrap(),
+            to: Account::try_from(to.to_string()).unwrap(),
+            amount,
+            currency: None,
+            description: None,
+            meta: None,
+            exchange: None,
+        }
+    }
+
+    #[test]
+    fn test_broken_rules() {
+        let config = config();
+        assert!(transaction("user:a", "pos:fridge", 250)
+            .broken_rules(&config)
+            .is_empty());
+        assert!(transaction("source:cash", "user:a", 2000)
+            .broken_rules(&config)
+            .is_empty());
+        assert_eq!(
+            transaction("pos:fridge", "user:a", 250).broken_rules(&config),
+            vec![Rule::PointOfSaleCannotSend]
+        );
+        assert_eq!(
+            transaction("user:a", "pos:fridge", -250).broken_rules(&config),
+            vec![Rule::PointOfSaleCannotSend]
+        );
+        assert_eq!(
+            transaction("pos:fridge", "source:cash", 1).broken_rules(&config),
+            vec![Rule::PointOfSaleCannotSend, Rule::SourceCannotReceive]
+        );
+    }
+
+    #[test]
+    fn test_broken_currency_rules() {
+        let config = config();
+        let mut transaction = transaction("user:a", "user:bank", 1000);
+        transaction.currency = Some("EUR".into());
+        transaction.exchange = Some(Exchange {
+            currency: "CHF".into(),
+            amount: 950,
+            rate: "0.95".into(),
+        });
+        assert!(transaction.broken_rules(&config).is_empty());
+
+        transaction.exchange.as_mut().unwrap().currency = "EUR".into();
+        assert_eq!(
+            transaction.broken_rules(&config),
+            vec![Rule::ExchangeWithinCurrency]
+        );
+        transaction.exchange.as_mut().unwrap().currency = "USD".into();
+        assert_eq!(
+            transaction.broken_rules(&config),
+            vec![Rule::UnknownCurrency]
+        );
+
+        // The exchange is paid back by the `to` account
+        transaction.exchange.as_mut().unwrap().currency = "CHF".into();
+        transaction.to = Account::point_of_sale("fridge").unwrap();
+        assert_eq!(
+            transaction.broken_rules(&config),
+            vec![Rule::PointOfSaleCannotSend]
+        );
+    }
+
+    #[test]
+    fn test_check_transaction() {
+        let test_repo = TestRepo::new();
+        test_repo.commit_transaction(
+            "Deposit",
+            "from = \"source:cash\"\nto = \"user:a\"\namount = 2000",
+        );
+        let repo = Repo::open(test_repo.path()).unwrap();
+        let options = TransactionOptions::default();
+        assert!(repo
+            .check_transaction(&transaction("user:a", "pos:fridge", 250), &options)
+            .is_ok());
+
+        let mut invalid_ean = transaction("user:a", "pos:fridge", 250);
+        invalid_ean.meta = Some(TransactionMeta {
+            class: None,
+            ean: Some(7610867035004),
+            import_hash: None,
+        });
+        for transaction in [
+            transaction("user:a", "pos:fridge", 0),
+            transaction("user:a", "pos:fridge", -250),
+            transaction("user:b", "pos:fridge", 250),
+            transaction("pos:fridge", "user:a", 250),
+            invalid_ean,
+        ] {
+            let err = repo.check_transaction(&transaction, &options).unwrap_err();
+            assert!(matches!(err, Error::ValidationError(_)), "{}", err);
+        }
+    }
+}